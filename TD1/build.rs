@@ -0,0 +1,4 @@
+// Re-embed migrations when files in `migrations/` change.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
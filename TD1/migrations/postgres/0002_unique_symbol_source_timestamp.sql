@@ -0,0 +1,12 @@
+-- One row per (symbol, source, timestamp) so retries and overlapping runs
+-- don't write duplicates. Tables created before this migration may already
+-- hold duplicates; keep the oldest row of each.
+DELETE FROM stock_prices a
+USING stock_prices b
+WHERE a.id > b.id
+  AND a.symbol = b.symbol
+  AND a.source = b.source
+  AND a.timestamp = b.timestamp;
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_symbol_source_timestamp
+ON stock_prices(symbol, source, timestamp);
@@ -0,0 +1,6 @@
+-- Store prices exactly instead of as binary floating point.
+ALTER TABLE stock_prices
+    ALTER COLUMN price TYPE NUMERIC(18, 6) USING price::NUMERIC(18, 6);
+
+ALTER TABLE stock_prices
+    ADD COLUMN IF NOT EXISTS currency CHAR(3) NOT NULL DEFAULT 'USD';
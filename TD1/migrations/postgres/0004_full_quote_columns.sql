@@ -0,0 +1,11 @@
+-- Keep the whole daily snapshot, not just the last price. Sources that
+-- don't report a field leave it NULL.
+ALTER TABLE stock_prices
+    ADD COLUMN IF NOT EXISTS open NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS high NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS low NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS volume BIGINT,
+    ADD COLUMN IF NOT EXISTS previous_close NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS change NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS change_percent NUMERIC(18, 6),
+    ADD COLUMN IF NOT EXISTS trading_day DATE;
@@ -0,0 +1,22 @@
+-- Replace the BIGINT `timestamp` with the provider's quote time, and turn
+-- the naive `created_at` into our ingestion time, both as TIMESTAMPTZ.
+ALTER TABLE stock_prices ADD COLUMN IF NOT EXISTS quoted_at TIMESTAMPTZ;
+UPDATE stock_prices SET quoted_at = to_timestamp(timestamp) WHERE quoted_at IS NULL;
+ALTER TABLE stock_prices ALTER COLUMN quoted_at SET NOT NULL;
+
+-- `created_at` was filled with CURRENT_TIMESTAMP in the session time zone.
+ALTER TABLE stock_prices ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE stock_prices RENAME COLUMN created_at TO fetched_at;
+UPDATE stock_prices SET fetched_at = quoted_at WHERE fetched_at IS NULL;
+ALTER TABLE stock_prices ALTER COLUMN fetched_at SET DEFAULT now();
+ALTER TABLE stock_prices ALTER COLUMN fetched_at SET NOT NULL;
+
+DROP INDEX IF EXISTS uq_symbol_source_timestamp;
+DROP INDEX IF EXISTS idx_symbol_timestamp;
+ALTER TABLE stock_prices DROP COLUMN timestamp;
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_symbol_source_quoted_at
+ON stock_prices(symbol, source, quoted_at);
+
+CREATE INDEX IF NOT EXISTS idx_symbol_quoted_at
+ON stock_prices(symbol, quoted_at DESC);
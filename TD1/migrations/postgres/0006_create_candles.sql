@@ -0,0 +1,29 @@
+-- OHLCV candles rolled up from stock_prices by the aggregation job.
+-- `opened_at` and `closed_at` are the quote times of the first and last
+-- tick in the candle, so the aggregator can tell a late tick from one it
+-- already counted once raw ticks are pruned. Only daily candles have a
+-- volume: sources report the day's cumulative volume.
+CREATE TABLE IF NOT EXISTS candles (
+    symbol VARCHAR(10) NOT NULL,
+    resolution VARCHAR(3) NOT NULL,
+    bucket_start TIMESTAMPTZ NOT NULL,
+    open NUMERIC(18, 6) NOT NULL,
+    high NUMERIC(18, 6) NOT NULL,
+    low NUMERIC(18, 6) NOT NULL,
+    close NUMERIC(18, 6) NOT NULL,
+    volume BIGINT,
+    tick_count INTEGER NOT NULL,
+    opened_at TIMESTAMPTZ NOT NULL,
+    closed_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, resolution, bucket_start)
+);
+
+-- Each tick records whether it has been rolled into candles. Ids can't
+-- stand in for that: concurrent writers may commit a lower id after a
+-- higher one.
+ALTER TABLE stock_prices
+ADD COLUMN IF NOT EXISTS aggregated BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_stock_prices_pending
+ON stock_prices(id) WHERE NOT aggregated;
@@ -0,0 +1,26 @@
+-- Same shape as the Postgres schema after its migration 0005. SQLite has no
+-- exact decimal type, so prices are kept as TEXT and parsed on read.
+-- Timestamps are RFC 3339 UTC text, which sorts chronologically.
+CREATE TABLE IF NOT EXISTS stock_prices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol TEXT NOT NULL,
+    price TEXT NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    source TEXT NOT NULL,
+    quoted_at TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    open TEXT,
+    high TEXT,
+    low TEXT,
+    volume INTEGER,
+    previous_close TEXT,
+    change TEXT,
+    change_percent TEXT,
+    trading_day TEXT
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_symbol_source_quoted_at
+ON stock_prices(symbol, source, quoted_at);
+
+CREATE INDEX IF NOT EXISTS idx_symbol_quoted_at
+ON stock_prices(symbol, quoted_at DESC);
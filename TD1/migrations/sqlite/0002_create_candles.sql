@@ -0,0 +1,26 @@
+-- Same shape as the Postgres schema after its migration 0006.
+-- `opened_at` and `closed_at` are the quote times of the first and last
+-- tick in the candle, so the aggregator can tell a late tick from one it
+-- already counted once raw ticks are pruned. Only daily candles have a
+-- volume: sources report the day's cumulative volume.
+CREATE TABLE IF NOT EXISTS candles (
+    symbol TEXT NOT NULL,
+    resolution TEXT NOT NULL,
+    bucket_start TEXT NOT NULL,
+    open TEXT NOT NULL,
+    high TEXT NOT NULL,
+    low TEXT NOT NULL,
+    close TEXT NOT NULL,
+    volume INTEGER,
+    tick_count INTEGER NOT NULL,
+    opened_at TEXT NOT NULL,
+    closed_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (symbol, resolution, bucket_start)
+);
+
+-- Each tick records whether it has been rolled into candles.
+ALTER TABLE stock_prices ADD COLUMN aggregated INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_stock_prices_pending
+ON stock_prices(id) WHERE aggregated = 0;
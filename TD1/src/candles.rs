@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio::time::{Duration, MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::scheduler::Scheduler;
+use crate::store::{PriceStore, StoreError};
+
+/// New ticks read from the store per step of an aggregation pass.
+const TICKS_PER_STEP: usize = 5_000;
+
+// --- Resolutions ---
+
+/// Candle width. Buckets are aligned to the Unix epoch, so daily candles
+/// run from midnight to midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum Resolution {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 4] = [
+        Resolution::OneMinute,
+        Resolution::FiveMinutes,
+        Resolution::OneHour,
+        Resolution::OneDay,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resolution::OneMinute => "1m",
+            Resolution::FiveMinutes => "5m",
+            Resolution::OneHour => "1h",
+            Resolution::OneDay => "1d",
+        }
+    }
+
+    pub fn duration(self) -> TimeDelta {
+        match self {
+            Resolution::OneMinute => TimeDelta::minutes(1),
+            Resolution::FiveMinutes => TimeDelta::minutes(5),
+            Resolution::OneHour => TimeDelta::hours(1),
+            Resolution::OneDay => TimeDelta::days(1),
+        }
+    }
+
+    /// Whether candles this wide carry a volume. Sources report the day's
+    /// cumulative volume, which doesn't say how much traded within a
+    /// narrower bucket.
+    pub fn has_volume(self) -> bool {
+        self == Resolution::OneDay
+    }
+
+    /// Start of the bucket holding `t`.
+    pub fn bucket_start(self, t: DateTime<Utc>) -> DateTime<Utc> {
+        let width = self.duration().num_seconds();
+        let start = t.timestamp().div_euclid(width) * width;
+        DateTime::from_timestamp(start, 0).expect("bucket start is within chrono's range")
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Resolution::ALL
+            .into_iter()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| format!("{s:?} (expected 1m, 5m, 1h or 1d)"))
+    }
+}
+
+// --- Candles ---
+
+/// A stored price as the aggregator sees it. Stored prices are flagged
+/// once rolled into candles, and `id` is what the aggregator flags them by.
+/// Ids don't tell which ticks are new: concurrent writers can commit a
+/// lower id after a higher one.
+#[derive(Debug, Clone)]
+pub struct Tick {
+    pub id: i64,
+    pub symbol: String,
+    pub price: Decimal,
+    pub volume: Option<i64>,
+    pub quoted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candle {
+    pub symbol: String,
+    pub resolution: Resolution,
+    pub bucket_start: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// The volume traded that day as of the candle's last tick. Only daily
+    /// candles have one, see [`Resolution::has_volume`].
+    pub volume: Option<i64>,
+    pub tick_count: i64,
+    /// Quote times of the first and last tick rolled into the candle.
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: DateTime<Utc>,
+}
+
+impl Candle {
+    /// Folds a late `tick` into a candle whose own ticks may be gone. A tick
+    /// quoted within the span the candle already covers is skipped: once its
+    /// ticks are pruned, a quote stored again looks just like a new one.
+    pub fn absorb(&mut self, tick: &Tick) {
+        if (self.opened_at..=self.closed_at).contains(&tick.quoted_at) {
+            return;
+        }
+        if tick.quoted_at < self.opened_at {
+            self.open = tick.price;
+            self.opened_at = tick.quoted_at;
+        } else {
+            self.close = tick.price;
+            self.closed_at = tick.quoted_at;
+        }
+        self.high = self.high.max(tick.price);
+        self.low = self.low.min(tick.price);
+        if self.resolution.has_volume() {
+            self.volume = self.volume.max(tick.volume);
+        }
+        self.tick_count += 1;
+    }
+}
+
+/// Builds one candle per `resolution` bucket from `ticks`, which must all
+/// be for `symbol` and sorted by quote time.
+pub fn build_candles(symbol: &str, resolution: Resolution, ticks: &[Tick]) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = Vec::new();
+    for tick in ticks {
+        let start = resolution.bucket_start(tick.quoted_at);
+        let volume = tick.volume.filter(|_| resolution.has_volume());
+        match candles.last_mut() {
+            Some(c) if c.bucket_start == start => {
+                c.high = c.high.max(tick.price);
+                c.low = c.low.min(tick.price);
+                c.close = tick.price;
+                c.volume = volume.or(c.volume);
+                c.tick_count += 1;
+                c.closed_at = tick.quoted_at;
+            }
+            _ => candles.push(Candle {
+                symbol: symbol.to_string(),
+                resolution,
+                bucket_start: start,
+                open: tick.price,
+                high: tick.price,
+                low: tick.price,
+                close: tick.price,
+                volume,
+                tick_count: 1,
+                opened_at: tick.quoted_at,
+                closed_at: tick.quoted_at,
+            }),
+        }
+    }
+    candles
+}
+
+// --- Aggregation job ---
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CandleConfig {
+    /// Run the aggregator alongside `run`.
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub resolutions: Vec<Resolution>,
+}
+
+impl Default for CandleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 60,
+            resolutions: Resolution::ALL.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AggregateReport {
+    pub ticks: usize,
+    pub candles: usize,
+}
+
+impl fmt::Display for AggregateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} new ticks, {} candles updated", self.ticks, self.candles)
+    }
+}
+
+/// Rolls ticks up into candles. Each pass picks up the ticks not yet rolled
+/// up and rebuilds every bucket they fall in from all of that bucket's
+/// ticks, so late ticks land in the right candle.
+pub struct Aggregator {
+    store: Arc<dyn PriceStore>,
+    resolutions: Vec<Resolution>,
+    /// How long raw ticks are kept before pruning may delete them.
+    raw_retention: Option<TimeDelta>,
+}
+
+impl Aggregator {
+    pub fn new(
+        store: Arc<dyn PriceStore>,
+        resolutions: &[Resolution],
+        raw_retention: Option<TimeDelta>,
+    ) -> Self {
+        let mut resolutions = resolutions.to_vec();
+        resolutions.sort();
+        resolutions.dedup();
+        Self { store, resolutions, raw_retention }
+    }
+
+    /// A bucket older than raw retention may have lost ticks to pruning,
+    /// so a rebuild could shrink it. Unless the rebuild still spans the
+    /// stored candle, the new ticks are folded into the stored candle.
+    /// Pruning goes by quote time, so a bucket that kept its first tick
+    /// kept them all.
+    async fn merge_late(&self, rebuilt: Candle, ticks: &[Tick]) -> Result<Candle, StoreError> {
+        let Some(mut stored) = self
+            .store
+            .find_candle(&rebuilt.symbol, rebuilt.resolution, rebuilt.bucket_start)
+            .await?
+        else {
+            return Ok(rebuilt);
+        };
+        if rebuilt.opened_at <= stored.opened_at && rebuilt.closed_at >= stored.closed_at {
+            return Ok(rebuilt);
+        }
+
+        let end = stored.bucket_start + stored.resolution.duration();
+        for tick in ticks {
+            if tick.symbol == stored.symbol && (stored.bucket_start..end).contains(&tick.quoted_at) {
+                stored.absorb(tick);
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Aggregates every tick not yet rolled into candles.
+    pub async fn run_once(&self) -> Result<AggregateReport, StoreError> {
+        let mut report = AggregateReport::default();
+        let Some(&widest) = self.resolutions.last() else {
+            return Ok(report);
+        };
+
+        let raw_cutoff = self.raw_retention.map(|keep| Utc::now() - keep);
+
+        loop {
+            let ticks = self.store.pending_ticks(TICKS_PER_STEP).await?;
+            if ticks.is_empty() {
+                break;
+            }
+            report.ticks += ticks.len();
+
+            let mut touched: BTreeSet<(&str, Resolution, DateTime<Utc>)> = BTreeSet::new();
+            let mut spans: BTreeMap<&str, BTreeSet<DateTime<Utc>>> = BTreeMap::new();
+            for tick in &ticks {
+                for &res in &self.resolutions {
+                    touched.insert((&tick.symbol, res, res.bucket_start(tick.quoted_at)));
+                }
+                spans.entry(&tick.symbol).or_default().insert(widest.bucket_start(tick.quoted_at));
+            }
+
+            // Smaller buckets nest inside the widest one, so loading each
+            // touched widest bucket is enough to rebuild all of them. A
+            // rebuild may take in ticks still pending; rebuilding their
+            // buckets again when their turn comes changes nothing.
+            let mut candles = Vec::new();
+            for (symbol, starts) in spans {
+                for start in starts {
+                    let span = self
+                        .store
+                        .ticks_between(symbol, start, start + widest.duration())
+                        .await?;
+                    for &res in &self.resolutions {
+                        for candle in build_candles(symbol, res, &span) {
+                            if !touched.contains(&(symbol, res, candle.bucket_start)) {
+                                continue;
+                            }
+                            if raw_cutoff.is_some_and(|cutoff| candle.bucket_start < cutoff) {
+                                candles.push(self.merge_late(candle, &ticks).await?);
+                            } else {
+                                candles.push(candle);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let ids: Vec<i64> = ticks.iter().map(|t| t.id).collect();
+            self.store.save_candles(&candles, &ids).await?;
+            report.candles += candles.len();
+        }
+
+        Ok(report)
+    }
+
+    /// Runs a pass every `period` until shutdown.
+    pub async fn run(self, period: Duration, shutdown: watch::Receiver<bool>) {
+        let scheduler = Scheduler::new("Aggregating candles", period, MissedTickBehavior::Skip);
+        scheduler
+            .run(shutdown, || async {
+                match self.run_once().await {
+                    Ok(report) if report.ticks > 0 => info!("Aggregated candles ({report})"),
+                    Ok(_) => debug!("No new ticks to aggregate"),
+                    Err(e) => error!("Candle aggregation failed: {e}"),
+                }
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn tick(id: i64, cents: i64, h: u32, m: u32) -> Tick {
+        Tick {
+            id,
+            symbol: "AAPL".to_string(),
+            price: Decimal::new(cents, 2),
+            volume: Some(id * 100),
+            quoted_at: Utc.with_ymd_and_hms(2026, 9, 1, h, m, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn bucket_start_aligns_to_the_epoch() {
+        let t = Utc.with_ymd_and_hms(2026, 9, 1, 14, 37, 12).unwrap();
+        assert_eq!(Resolution::FiveMinutes.bucket_start(t), Utc.with_ymd_and_hms(2026, 9, 1, 14, 35, 0).unwrap());
+        assert_eq!(Resolution::OneDay.bucket_start(t), Utc.with_ymd_and_hms(2026, 9, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn build_candles_groups_ticks_by_bucket() {
+        let ticks = [tick(1, 10_000, 14, 1), tick(2, 10_300, 14, 2), tick(3, 9_800, 14, 4), tick(4, 9_900, 14, 6)];
+        let candles = build_candles("AAPL", Resolution::FiveMinutes, &ticks);
+
+        assert_eq!(candles.len(), 2);
+        let first = &candles[0];
+        assert_eq!(first.bucket_start, Utc.with_ymd_and_hms(2026, 9, 1, 14, 0, 0).unwrap());
+        assert_eq!(
+            (first.open, first.high, first.low, first.close),
+            (Decimal::new(10_000, 2), Decimal::new(10_300, 2), Decimal::new(9_800, 2), Decimal::new(9_800, 2))
+        );
+        assert_eq!((first.volume, first.tick_count), (None, 3));
+        assert_eq!((first.opened_at, first.closed_at), (ticks[0].quoted_at, ticks[2].quoted_at));
+        assert_eq!((candles[1].open, candles[1].tick_count), (Decimal::new(9_900, 2), 1));
+    }
+
+    #[test]
+    fn only_daily_candles_carry_the_cumulative_volume() {
+        let ticks = [tick(1, 10_000, 14, 1), tick(2, 10_300, 15, 2), tick(3, 9_800, 16, 4)];
+        let daily = build_candles("AAPL", Resolution::OneDay, &ticks);
+        assert_eq!(daily[0].volume, Some(300));
+
+        let mut hourly = build_candles("AAPL", Resolution::OneHour, &ticks);
+        assert!(hourly.iter().all(|c| c.volume.is_none()));
+        hourly[0].absorb(&tick(4, 10_000, 14, 30));
+        assert_eq!(hourly[0].volume, None);
+    }
+
+    #[test]
+    fn absorb_skips_ticks_within_the_candle_span() {
+        let mut candle = build_candles("AAPL", Resolution::OneHour, &[tick(1, 10_000, 14, 10), tick(2, 10_100, 14, 20)])
+            .remove(0);
+
+        candle.absorb(&tick(3, 12_000, 14, 15));
+        assert_eq!((candle.high, candle.tick_count), (Decimal::new(10_100, 2), 2));
+
+        candle.absorb(&tick(4, 9_500, 14, 5));
+        candle.absorb(&tick(5, 10_200, 14, 50));
+        assert_eq!((candle.open, candle.close), (Decimal::new(9_500, 2), Decimal::new(10_200, 2)));
+        assert_eq!((candle.low, candle.high, candle.tick_count), (Decimal::new(9_500, 2), Decimal::new(10_200, 2), 4));
+        assert_eq!((candle.opened_at, candle.closed_at), (tick(0, 0, 14, 5).quoted_at, tick(0, 0, 14, 50).quoted_at));
+    }
+}
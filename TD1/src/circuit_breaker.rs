@@ -0,0 +1,197 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Calls go through normally.
+    Closed,
+    /// The source is failing; calls are skipped until the cooldown ends.
+    Open,
+    /// The cooldown ended; a single probe call decides whether to close again.
+    HalfOpen,
+}
+
+impl fmt::Display for BreakerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerState::Closed => write!(f, "closed"),
+            BreakerState::Open => write!(f, "open"),
+            BreakerState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakerConfig {
+    /// Consecutive failures before the circuit opens.
+    pub failure_threshold: u32,
+    pub cooldown_secs: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown_secs: 60,
+        }
+    }
+}
+
+impl BreakerConfig {
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// Per-source circuit breaker. State changes are logged, and the current
+/// state can be read with [`state`](Self::state).
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    source: String,
+    config: BreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(source: &str, config: BreakerConfig) -> Self {
+        Self {
+            source: source.to_string(),
+            config,
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Whether a call may be made now. In half-open state only one probe
+    /// is let through at a time.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => {
+                let cooled_down = inner
+                    .opened_at
+                    .is_some_and(|at| at.elapsed() >= self.config.cooldown());
+                if cooled_down {
+                    inner.probe_in_flight = true;
+                    self.transition(&mut inner, BreakerState::HalfOpen);
+                }
+                cooled_down
+            }
+            BreakerState::HalfOpen => !std::mem::replace(&mut inner.probe_in_flight, true),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+        self.transition(&mut inner, BreakerState::Closed);
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        inner.probe_in_flight = false;
+
+        let trip = match inner.state {
+            BreakerState::HalfOpen => true,
+            BreakerState::Closed => inner.consecutive_failures >= self.config.failure_threshold,
+            BreakerState::Open => false,
+        };
+        if trip {
+            inner.opened_at = Some(Instant::now());
+            self.transition(&mut inner, BreakerState::Open);
+        }
+    }
+
+    /// Gives back a slot taken by [`allow`](Self::allow) when the call was
+    /// never made (e.g. skipped by the rate limiter).
+    pub fn release(&self) {
+        self.inner.lock().unwrap().probe_in_flight = false;
+    }
+
+    fn transition(&self, inner: &mut Inner, to: BreakerState) {
+        let from = std::mem::replace(&mut inner.state, to);
+        if from == to {
+            return;
+        }
+        match to {
+            BreakerState::Open => warn!(
+                source = %self.source,
+                cooldown = ?self.config.cooldown(),
+                "Circuit {from} -> {to}, pausing calls"
+            ),
+            _ => info!(source = %self.source, "Circuit {from} -> {to}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32, cooldown_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new("test", BreakerConfig { failure_threshold, cooldown_secs })
+    }
+
+    #[test]
+    fn opens_after_threshold_and_stays_open_during_cooldown() {
+        let breaker = breaker(2, 60);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let breaker = breaker(2, 60);
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn half_open_lets_one_probe_through() {
+        let breaker = breaker(1, 0);
+        breaker.record_failure();
+        assert!(breaker.allow());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.allow());
+
+        breaker.release();
+        assert!(breaker.allow());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow() && breaker.allow());
+    }
+}
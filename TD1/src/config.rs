@@ -0,0 +1,607 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::candles::{CandleConfig, Resolution};
+use crate::circuit_breaker::BreakerConfig;
+use crate::http::HttpConfig;
+use crate::rate_limit::Quota;
+use crate::retention::RetentionConfig;
+use crate::retry::RetryPolicy;
+use crate::spool::SpoolConfig;
+use crate::store::Backend;
+use crate::writer::{WriterConfig, MAX_BATCH_SIZE};
+
+/// Sources `sources::build_sources` knows how to construct.
+pub const KNOWN_SOURCES: &[&str] = &["alpha_vantage", "finnhub", "mock"];
+
+const DEFAULT_CONFIG_PATH: &str = "td1.toml";
+
+// --- Errors ---
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read config {}: {e}", path.display()),
+            ConfigError::Parse(path, e) => write!(f, "invalid config {}: {e}", path.display()),
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn invalid(msg: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid(msg.into())
+}
+
+// --- CLI flags ---
+
+/// Flags that override the config file and environment.
+#[derive(clap::Args, Debug, Default)]
+pub struct ConfigArgs {
+    /// Path to the TOML config file [env: TD1_CONFIG] [default: td1.toml]
+    #[arg(long, short = 'c', global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Storage backend (postgres, sqlite, memory)
+    #[arg(long, global = true)]
+    pub backend: Option<Backend>,
+
+    /// Database connection string, e.g. postgres://... or sqlite://td1.db
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+
+    /// Comma separated symbols to track
+    #[arg(long, value_delimiter = ',', global = true)]
+    pub symbols: Option<Vec<String>>,
+
+    /// Comma separated price sources (alpha_vantage, finnhub, mock)
+    #[arg(long, value_delimiter = ',', global = true)]
+    pub sources: Option<Vec<String>>,
+
+    /// Seconds between fetch cycles
+    #[arg(long, global = true)]
+    pub interval_secs: Option<u64>,
+
+    /// Maximum number of fetches running at once
+    #[arg(long, global = true)]
+    pub concurrency: Option<usize>,
+}
+
+// --- Config ---
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub symbols: Vec<String>,
+    pub sources: Vec<String>,
+    pub database: DatabaseConfig,
+    pub fetch: FetchConfig,
+    pub http: HttpConfig,
+    pub writer: WriterConfig,
+    pub spool: SpoolConfig,
+    pub candles: CandleConfig,
+    pub retention: RetentionConfig,
+    pub providers: BTreeMap<String, ProviderConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            symbols: vec!["AAPL".to_string(), "GOOGL".to_string(), "MSFT".to_string()],
+            sources: vec!["alpha_vantage".to_string()],
+            database: DatabaseConfig::default(),
+            fetch: FetchConfig::default(),
+            http: HttpConfig::default(),
+            writer: WriterConfig::default(),
+            spool: SpoolConfig::default(),
+            candles: CandleConfig::default(),
+            retention: RetentionConfig::default(),
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub backend: Backend,
+    /// Required by the postgres and sqlite backends.
+    pub url: Option<String>,
+    pub max_connections: u32,
+    /// Apply pending migrations when TD1 starts.
+    pub auto_migrate: bool,
+    /// Postgres channel to NOTIFY with every newly inserted price, for TD2
+    /// to relay to its clients. `backfill` doesn't notify.
+    pub notify_channel: Option<String>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            url: None,
+            max_connections: 5,
+            auto_migrate: false,
+            notify_channel: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    pub interval_secs: u64,
+    pub missed_tick: MissedTick,
+    pub concurrency: usize,
+    /// Warn about quotes older than this when fetched. 0 disables the check.
+    pub stale_after_secs: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            missed_tick: MissedTick::Skip,
+            concurrency: 8,
+            stale_after_secs: 0,
+        }
+    }
+}
+
+/// What the scheduler does when a cycle overruns the next tick.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissedTick {
+    Burst,
+    Delay,
+    Skip,
+}
+
+impl FromStr for MissedTick {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "burst" => Ok(MissedTick::Burst),
+            "delay" => Ok(MissedTick::Delay),
+            "skip" => Ok(MissedTick::Skip),
+            other => Err(format!("{other:?} (expected burst, delay or skip)")),
+        }
+    }
+}
+
+impl From<MissedTick> for MissedTickBehavior {
+    fn from(m: MissedTick) -> Self {
+        match m {
+            MissedTick::Burst => MissedTickBehavior::Burst,
+            MissedTick::Delay => MissedTickBehavior::Delay,
+            MissedTick::Skip => MissedTickBehavior::Skip,
+        }
+    }
+}
+
+/// Per-source settings, under `[providers.<name>]`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub api_key: Option<String>,
+    /// Falls back to [`Quota::default_for`] when unset.
+    pub quota: Option<Quota>,
+    pub retry: RetryPolicy,
+    pub breaker: BreakerConfig,
+}
+
+impl Config {
+    /// Loads the config file, then applies environment variables, then
+    /// CLI flags, and validates the result.
+    pub fn load(args: &ConfigArgs) -> Result<Self, ConfigError> {
+        let explicit = args
+            .config
+            .clone()
+            .or_else(|| std::env::var_os("TD1_CONFIG").map(PathBuf::from));
+
+        let mut config = match &explicit {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_args(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_parse("DATABASE_BACKEND", &mut self.database.backend)?;
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            self.database.url = Some(url);
+        }
+        env_parse("DATABASE_MAX_CONNECTIONS", &mut self.database.max_connections)?;
+        env_parse("DATABASE_AUTO_MIGRATE", &mut self.database.auto_migrate)?;
+        if let Ok(channel) = std::env::var("DATABASE_NOTIFY_CHANNEL") {
+            self.database.notify_channel = (!channel.is_empty()).then_some(channel);
+        }
+
+        if let Ok(v) = std::env::var("SYMBOLS") {
+            self.symbols = split_list(&v);
+        }
+        if let Ok(v) = std::env::var("PRICE_SOURCES") {
+            self.sources = split_list(&v);
+        }
+
+        env_parse("FETCH_INTERVAL_SECS", &mut self.fetch.interval_secs)?;
+        env_parse("FETCH_MISSED_TICK", &mut self.fetch.missed_tick)?;
+        env_parse("FETCH_CONCURRENCY", &mut self.fetch.concurrency)?;
+        env_parse("FETCH_STALE_AFTER_SECS", &mut self.fetch.stale_after_secs)?;
+
+        env_parse("HTTP_CONNECT_TIMEOUT_SECS", &mut self.http.connect_timeout_secs)?;
+        env_parse("HTTP_REQUEST_TIMEOUT_SECS", &mut self.http.request_timeout_secs)?;
+        env_parse("HTTP_USER_AGENT", &mut self.http.user_agent)?;
+        env_parse("HTTP_POOL_MAX_IDLE_PER_HOST", &mut self.http.pool_max_idle_per_host)?;
+        env_parse("HTTP_POOL_IDLE_TIMEOUT_SECS", &mut self.http.pool_idle_timeout_secs)?;
+
+        env_parse("WRITER_BATCH_SIZE", &mut self.writer.batch_size)?;
+        env_parse("WRITER_FLUSH_INTERVAL_MS", &mut self.writer.flush_interval_ms)?;
+        env_parse("WRITER_CHANNEL_CAPACITY", &mut self.writer.channel_capacity)?;
+
+        env_parse("SPOOL_ENABLED", &mut self.spool.enabled)?;
+        env_parse("SPOOL_PATH", &mut self.spool.path)?;
+        env_parse("SPOOL_MAX_BYTES", &mut self.spool.max_bytes)?;
+        env_parse("SPOOL_RETRY_SECS", &mut self.spool.retry_secs)?;
+
+        env_parse("CANDLES_ENABLED", &mut self.candles.enabled)?;
+        env_parse("CANDLES_INTERVAL_SECS", &mut self.candles.interval_secs)?;
+        if let Ok(v) = std::env::var("CANDLES_RESOLUTIONS") {
+            self.candles.resolutions = split_list(&v)
+                .iter()
+                .map(|r| r.parse::<Resolution>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(format!("CANDLES_RESOLUTIONS: {e}")))?;
+        }
+
+        env_parse("RETENTION_ENABLED", &mut self.retention.enabled)?;
+        env_parse("RETENTION_INTERVAL_SECS", &mut self.retention.interval_secs)?;
+        env_parse("RETENTION_RAW_DAYS", &mut self.retention.raw_days)?;
+        for resolution in Resolution::ALL {
+            let key = format!("RETENTION_CANDLES_{}_DAYS", resolution.as_str().to_uppercase());
+            let mut days = self.retention.candles.get(&resolution).copied().unwrap_or(0);
+            if env_parse(&key, &mut days)? {
+                self.retention.candles.insert(resolution, days);
+            }
+        }
+
+        for &name in KNOWN_SOURCES {
+            let prefix = name.to_uppercase();
+            let provider = self.providers.entry(name.to_string()).or_default();
+
+            if let Ok(key) = std::env::var(format!("{prefix}_KEY")) {
+                provider.api_key = Some(key);
+            }
+
+            let mut quota = provider.quota.unwrap_or_else(|| Quota::default_for(name));
+            let minute_set = env_parse(&format!("{prefix}_RATE_PER_MINUTE"), &mut quota.per_minute)?;
+            let day_set = env_parse(&format!("{prefix}_RATE_PER_DAY"), &mut quota.per_day)?;
+            if minute_set || day_set {
+                provider.quota = Some(quota);
+            }
+
+            let retry = &mut provider.retry;
+            env_parse(&format!("{prefix}_RETRY_MAX_ATTEMPTS"), &mut retry.max_attempts)?;
+            env_parse(&format!("{prefix}_RETRY_BASE_MS"), &mut retry.base_delay_ms)?;
+            env_parse(&format!("{prefix}_RETRY_MAX_MS"), &mut retry.max_delay_ms)?;
+            env_parse(&format!("{prefix}_RETRY_JITTER"), &mut retry.jitter)?;
+
+            let breaker = &mut provider.breaker;
+            env_parse(&format!("{prefix}_BREAKER_THRESHOLD"), &mut breaker.failure_threshold)?;
+            env_parse(&format!("{prefix}_BREAKER_COOLDOWN_SECS"), &mut breaker.cooldown_secs)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(backend) = args.backend {
+            self.database.backend = backend;
+        }
+        if let Some(url) = &args.database_url {
+            self.database.url = Some(url.clone());
+        }
+        if let Some(symbols) = &args.symbols {
+            self.symbols = symbols.clone();
+        }
+        if let Some(sources) = &args.sources {
+            self.sources = sources.clone();
+        }
+        if let Some(secs) = args.interval_secs {
+            self.fetch.interval_secs = secs;
+        }
+        if let Some(n) = args.concurrency {
+            self.fetch.concurrency = n;
+        }
+    }
+
+    /// Checks the settings and normalizes the symbols in place.
+    fn validate(&mut self) -> Result<(), ConfigError> {
+        if self.symbols.is_empty() {
+            return Err(invalid("`symbols` must list at least one symbol"));
+        }
+        self.symbols = self
+            .symbols
+            .iter()
+            .map(|symbol| normalize_symbol(symbol))
+            .collect::<Result<_, _>>()
+            .map_err(invalid)?;
+        if self.sources.is_empty() {
+            return Err(invalid("`sources` must list at least one price source"));
+        }
+        for name in &self.sources {
+            if !KNOWN_SOURCES.contains(&name.as_str()) {
+                return Err(invalid(format!(
+                    "unknown source {name:?} in `sources` (expected one of {})",
+                    KNOWN_SOURCES.join(", ")
+                )));
+            }
+        }
+
+        if self.database.backend.needs_url() {
+            self.database_url()?;
+        }
+        if self.database.max_connections == 0 {
+            return Err(invalid("database.max_connections must be greater than 0"));
+        }
+        if let Some(channel) = &self.database.notify_channel {
+            if channel.is_empty() {
+                return Err(invalid("database.notify_channel must not be empty (leave it out to disable)"));
+            }
+            if self.database.backend != Backend::Postgres {
+                return Err(invalid(format!(
+                    "database.notify_channel needs the postgres backend, not {}",
+                    self.database.backend
+                )));
+            }
+        }
+        if self.fetch.interval_secs == 0 {
+            return Err(invalid("fetch.interval_secs must be greater than 0"));
+        }
+        if self.fetch.concurrency == 0 {
+            return Err(invalid("fetch.concurrency must be greater than 0"));
+        }
+        if self.http.connect_timeout_secs == 0 || self.http.request_timeout_secs == 0 {
+            return Err(invalid("http timeouts must be greater than 0"));
+        }
+
+        if !(1..=MAX_BATCH_SIZE).contains(&self.writer.batch_size) {
+            return Err(invalid(format!("writer.batch_size must be between 1 and {MAX_BATCH_SIZE}")));
+        }
+        if self.writer.flush_interval_ms == 0 {
+            return Err(invalid("writer.flush_interval_ms must be greater than 0"));
+        }
+        if self.writer.channel_capacity == 0 {
+            return Err(invalid("writer.channel_capacity must be greater than 0"));
+        }
+        if self.spool.enabled && self.spool.max_bytes == 0 {
+            return Err(invalid("spool.max_bytes must be greater than 0 (or set spool.enabled = false)"));
+        }
+        if self.candles.interval_secs == 0 {
+            return Err(invalid("candles.interval_secs must be greater than 0"));
+        }
+        if self.candles.resolutions.is_empty() {
+            return Err(invalid("candles.resolutions must list at least one resolution"));
+        }
+        if self.retention.interval_secs == 0 {
+            return Err(invalid("retention.interval_secs must be greater than 0"));
+        }
+        if let Some(raw) = self.retention.raw() {
+            // Once a bucket's raw prices are pruned, the aggregator folds
+            // late ticks into its stored candle. Were the candle pruned
+            // first, it would be rebuilt from the late ticks alone.
+            for (resolution, &days) in &self.retention.candles {
+                if self.retention.candles(*resolution).is_some_and(|keep| keep < raw) {
+                    return Err(invalid(format!(
+                        "retention.candles.{resolution} ({days} days) must not be shorter than retention.raw_days"
+                    )));
+                }
+            }
+        }
+
+        for (name, provider) in &self.providers {
+            let retry = &provider.retry;
+            if retry.max_attempts == 0 {
+                return Err(invalid(format!("providers.{name}.retry.max_attempts must be at least 1")));
+            }
+            if retry.base_delay_ms > retry.max_delay_ms {
+                return Err(invalid(format!(
+                    "providers.{name}.retry.base_delay_ms must not exceed max_delay_ms"
+                )));
+            }
+            if !(0.0..=1.0).contains(&retry.jitter) {
+                return Err(invalid(format!("providers.{name}.retry.jitter must be between 0 and 1")));
+            }
+            if provider.breaker.failure_threshold == 0 {
+                return Err(invalid(format!(
+                    "providers.{name}.breaker.failure_threshold must be at least 1"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn database_url(&self) -> Result<&str, ConfigError> {
+        self.database
+            .url
+            .as_deref()
+            .ok_or_else(|| invalid("database.url is required (or set DATABASE_URL)"))
+    }
+
+    pub fn provider(&self, name: &str) -> ProviderConfig {
+        self.providers.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn api_key(&self, name: &str) -> Result<String, ConfigError> {
+        self.providers
+            .get(name)
+            .and_then(|p| p.api_key.clone())
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| {
+                invalid(format!(
+                    "providers.{name}.api_key is required (or set {}_KEY)",
+                    name.to_uppercase()
+                ))
+            })
+    }
+}
+
+impl ProviderConfig {
+    pub fn quota(&self, name: &str) -> Quota {
+        self.quota.unwrap_or_else(|| Quota::default_for(name))
+    }
+}
+
+// --- Helpers ---
+
+/// Uppercases `symbol` and checks it looks like a ticker that fits the
+/// database's 10 character symbol column: 1 to 10 letters, digits, `.` or
+/// `-`.
+pub fn normalize_symbol(symbol: &str) -> Result<String, String> {
+    let valid = (1..=10).contains(&symbol.len())
+        && symbol.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if !valid {
+        return Err(format!("symbol {symbol:?} must be 1 to 10 letters, digits, `.` or `-`"));
+    }
+    Ok(symbol.to_ascii_uppercase())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Overwrites `target` when `key` is set. Returns whether it was set.
+fn env_parse<T>(key: &str, target: &mut T) -> Result<bool, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match std::env::var(key) {
+        Ok(v) => {
+            *target = v.parse().map_err(|e| invalid(format!("{key}: {e}")))?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `text`, filling in a database URL unless it sets one.
+    fn parse(text: &str) -> Config {
+        let mut config: Config = toml::from_str(text).unwrap();
+        config.database.url.get_or_insert_with(|| "postgres://localhost/td1".to_string());
+        config
+    }
+
+    fn error(text: &str) -> String {
+        parse(text).validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn file_settings_override_defaults() {
+        let mut config = parse(
+            r#"
+            symbols = ["AAPL"]
+            sources = ["finnhub"]
+
+            [fetch]
+            concurrency = 2
+
+            [providers.finnhub]
+            api_key = "key"
+            quota = { per_minute = 30 }
+            retry = { max_attempts = 5 }
+            "#,
+        );
+        config.validate().unwrap();
+        assert_eq!(config.symbols, ["AAPL"]);
+        assert_eq!((config.fetch.concurrency, config.fetch.interval_secs), (2, 60));
+
+        let finnhub = config.provider("finnhub");
+        assert_eq!(finnhub.quota("finnhub").per_minute, 30);
+        assert_eq!((finnhub.retry.max_attempts, finnhub.retry.base_delay_ms), (5, 500));
+        assert_eq!(config.provider("alpha_vantage").quota("alpha_vantage").per_day, 25);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[fetch]\nintervall_secs = 5").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_bad_settings() {
+        assert!(error("symbols = []").contains("at least one symbol"));
+        assert!(error(r#"symbols = ["TOOLONGSYMBOL"]"#).contains("1 to 10 letters"));
+        assert!(error(r#"symbols = ["AAPL&x=1"]"#).contains("1 to 10 letters"));
+        assert!(error(r#"sources = ["yahoo"]"#).contains("unknown source"));
+
+        let mock = "sources = [\"mock\"]\n";
+        assert!(error(&format!("{mock}[fetch]\nconcurrency = 0")).contains("fetch.concurrency"));
+        assert!(error(&format!("{mock}[providers.mock.retry]\njitter = 1.5")).contains("jitter"));
+        assert!(error(&format!("{mock}[providers.mock.retry]\nbase_delay_ms = 20000")).contains("max_delay_ms"));
+        assert!(error(&format!("{mock}[providers.mock.breaker]\nfailure_threshold = 0")).contains("failure_threshold"));
+    }
+
+    #[test]
+    fn cli_flags_override_the_file() {
+        let mut config = parse("sources = [\"mock\"]\n[fetch]\ninterval_secs = 30");
+        config.apply_args(&ConfigArgs {
+            symbols: Some(vec!["MSFT".to_string()]),
+            interval_secs: Some(5),
+            ..ConfigArgs::default()
+        });
+        assert_eq!(config.symbols, ["MSFT"]);
+        assert_eq!(config.fetch.interval_secs, 5);
+    }
+
+    #[test]
+    fn example_file_runs_on_any_backend() {
+        for backend in [Backend::Postgres, Backend::Sqlite, Backend::Memory] {
+            let mut config = parse(include_str!("../td1.example.toml"));
+            config.apply_args(&ConfigArgs { backend: Some(backend), ..ConfigArgs::default() });
+            config.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn validate_keeps_the_normalized_symbols() {
+        let mut config = parse(r#"symbols = ["aapl", "brk.b"]"#);
+        config.validate().unwrap();
+        assert_eq!(config.symbols, ["AAPL", "BRK.B"]);
+    }
+}
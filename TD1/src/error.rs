@@ -0,0 +1,77 @@
+use std::fmt;
+
+/// Why a price could not be fetched from a source.
+#[derive(Debug)]
+pub enum FetchError {
+    /// The provider is throttling us (HTTP 429 or a throttle notice in the body).
+    RateLimited(String),
+    /// The provider has no quote for this symbol.
+    UnknownSymbol(String),
+    /// The API key is missing, invalid or not allowed to make this call.
+    Auth(String),
+    /// The request never got a usable HTTP response.
+    Network(reqwest::Error),
+    /// The response did not have the shape we expected.
+    Malformed(String),
+    /// The source does not offer this kind of request.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::RateLimited(msg) => write!(f, "rate limited: {msg}"),
+            FetchError::UnknownSymbol(symbol) => write!(f, "unknown symbol: {symbol}"),
+            FetchError::Auth(msg) => write!(f, "authentication failed: {msg}"),
+            FetchError::Network(e) => write!(f, "network error: {e}"),
+            FetchError::Malformed(msg) => write!(f, "malformed response: {msg}"),
+            FetchError::Unsupported(what) => write!(f, "not supported by this source: {what}"),
+        }
+    }
+}
+
+impl FetchError {
+    /// Whether trying the same request again later might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchError::Network(e) => e.status().is_none_or(|s| s.is_server_error()),
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            FetchError::Malformed(e.to_string())
+        } else {
+            FetchError::Network(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for FetchError {
+    fn from(e: serde_json::Error) -> Self {
+        FetchError::Malformed(e.to_string())
+    }
+}
+
+/// Maps throttling and auth HTTP statuses to their `FetchError`, and
+/// passes every other response through.
+pub fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, FetchError> {
+    let status = resp.status();
+    match status.as_u16() {
+        401 | 403 => Err(FetchError::Auth(status.to_string())),
+        429 => Err(FetchError::RateLimited(status.to_string())),
+        _ => resp.error_for_status().map_err(FetchError::Network),
+    }
+}
@@ -0,0 +1,38 @@
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::time::Duration;
+
+/// Settings for the HTTP client shared by every price source.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub connect_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+    pub user_agent: String,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout_secs: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 5,
+            request_timeout_secs: 15,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout_secs: 90,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn build_client(&self) -> reqwest::Result<Client> {
+        Client::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .timeout(Duration::from_secs(self.request_timeout_secs))
+            .user_agent(&self.user_agent)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_secs))
+            .build()
+    }
+}
@@ -1,111 +1,467 @@
-use tokio::time::{sleep, Duration};
-use reqwest;
-use serde::Deserialize;
-use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
-use tracing::{info, error};
-use chrono::Utc;
+mod candles;
+mod circuit_breaker;
+mod config;
+mod error;
+mod http;
+mod models;
+mod provider;
+mod rate_limit;
+mod retention;
+mod retry;
+mod scheduler;
+mod sources;
+mod spool;
+mod store;
+mod writer;
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, SecondsFormat, TimeDelta, Utc};
+use clap::{Parser, Subcommand};
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::{mpsc, watch};
+use tokio::time::Duration;
+use tracing::{info, error, warn};
+use tracing_subscriber::EnvFilter;
 use dotenvy::dotenv;
 
-// --- Models ---
+use candles::{Aggregator, Resolution};
+use circuit_breaker::BreakerState;
+use config::{Config, ConfigArgs, ConfigError};
+use models::StockPrice;
+use retention::Pruner;
+use scheduler::Scheduler;
+use store::{PriceStore, StoreError};
+use writer::PriceWriter;
+use provider::Provider;
+
+// --- Fetch cycle ---
+
+/// Fetches every symbol from every source, running up to `concurrency`
+/// fetches at once. Each price is logged and queued for the writer as soon
+/// as it arrives.
+async fn fetch_and_save_all(
+    writer: &mpsc::Sender<StockPrice>,
+    providers: &[Provider],
+    symbols: &[String],
+    concurrency: usize,
+    stale_after: Option<TimeDelta>,
+    shutdown: &watch::Receiver<bool>,
+) {
+    info!(
+        "Starting fetch cycle for {} symbols across {} sources",
+        symbols.len(),
+        providers.len()
+    );
+
+    let jobs = symbols
+        .iter()
+        .flat_map(|sym| providers.iter().map(move |provider| (sym.as_str(), provider)));
+
+    let fetched = stream::iter(jobs)
+        .map(|(sym, provider)| async move {
+            // Don't start new requests once shutdown is requested; prices
+            // already fetched are still handed to the writer.
+            if *shutdown.borrow() {
+                return 0;
+            }
+
+            match provider.fetch(sym).await {
+                Ok(Some(price)) => {
+                    info!("Fetched {sym} from {}: {} {}", provider.name(), price.price, price.currency);
+                    if stale_after.is_some_and(|max| price.age() > max) {
+                        warn!(
+                            "Stale quote for {sym} from {}: quoted at {}, {}s old",
+                            provider.name(),
+                            price.quoted_at,
+                            price.age().num_seconds()
+                        );
+                    }
+                    if writer.send(price).await.is_err() {
+                        error!("Writer stopped, dropping {sym} price");
+                    }
+                    1
+                }
+                Ok(None) => 0,
+                Err(err) => {
+                    error!("Fetch error ({}) for {sym}: {err}", provider.name());
+                    0
+                }
+            }
+        })
+        .buffer_unordered(concurrency)
+        .fold(0, |total, n| async move { total + n })
+        .await;
+
+    for provider in providers {
+        let state = provider.breaker_state();
+        if state != BreakerState::Closed {
+            info!("Source {} circuit is {state}", provider.name());
+        }
+    }
 
-#[derive(Debug, Clone)]
-struct StockPrice {
-    symbol: String,
-    price: f64,
-    source: String,
-    timestamp: i64,
+    if *shutdown.borrow() {
+        info!("Fetch cycle interrupted by shutdown ({fetched} prices fetched)");
+    } else {
+        info!("Completed fetch cycle ({fetched} prices fetched)");
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct GlobalQuote {
-    #[serde(rename = "Global Quote")]
-    quote: Quote,
+// --- Commands ---
+
+fn stale_after(config: &Config) -> Option<TimeDelta> {
+    let secs = config.fetch.stale_after_secs;
+    (secs > 0).then(|| TimeDelta::seconds(secs as i64))
 }
 
-#[derive(Deserialize, Debug)]
-struct Quote {
-    #[serde(rename = "01. symbol")]
-    symbol: String,
-    #[serde(rename = "05. price")]
-    price: String,
+/// Runs fetch cycles on the configured interval until `shutdown`.
+async fn run(
+    writer: &mpsc::Sender<StockPrice>,
+    providers: &[Provider],
+    config: &Config,
+    shutdown: watch::Receiver<bool>,
+) {
+    let scheduler = Scheduler::new(
+        "Fetching prices",
+        Duration::from_secs(config.fetch.interval_secs),
+        config.fetch.missed_tick.into(),
+    );
+
+    scheduler
+        .run(shutdown.clone(), || {
+            fetch_and_save_all(
+                writer,
+                providers,
+                &config.symbols,
+                config.fetch.concurrency,
+                stale_after(config),
+                &shutdown,
+            )
+        })
+        .await;
 }
 
-// --- API Call ---
+async fn fetch_once(writer: &mpsc::Sender<StockPrice>, providers: &[Provider], config: &Config) {
+    let shutdown = scheduler::shutdown_signal();
+    fetch_and_save_all(
+        writer,
+        providers,
+        &config.symbols,
+        config.fetch.concurrency,
+        stale_after(config),
+        &shutdown,
+    )
+    .await;
+}
 
-async fn fetch_alpha_vantage(symbol: &str) -> Result<StockPrice, Box<dyn std::error::Error>> {
-    let api_key = std::env::var("ALPHA_VANTAGE_KEY")?;
+async fn backfill(
+    writer: &mpsc::Sender<StockPrice>,
+    providers: &[Provider],
+    symbol: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) {
+    info!("Backfilling {symbol} from {from} to {to}");
 
-    let url = format!(
-        "https://www.alphavantage.co/query?function=GLOBAL_QUOTE&symbol={symbol}&apikey={api_key}"
-    );
+    for provider in providers {
+        match provider.fetch_history(symbol, from, to).await {
+            Ok(Some(prices)) => {
+                info!("Fetched {} {symbol} prices from {}", prices.len(), provider.name());
+                for price in prices {
+                    if writer.send(price).await.is_err() {
+                        error!("Writer stopped, backfill of {symbol} incomplete");
+                        return;
+                    }
+                }
+            }
+            Ok(None) => info!("Skipped {} (quota or circuit open)", provider.name()),
+            Err(err) => error!("Backfill error ({}) for {symbol}: {err}", provider.name()),
+        }
+    }
+}
 
-    let resp = reqwest::get(&url)
-        .await?
-        .json::<GlobalQuote>()
-        .await?;
+async fn migrate(store: &dyn PriceStore, status_only: bool) -> Result<(), StoreError> {
+    print_migration_status(store).await?;
+    if status_only {
+        return Ok(());
+    }
 
-    let price = resp.quote.price.parse::<f64>()?;
+    store.migrate().await?;
+    println!();
+    print_migration_status(store).await
+}
 
-    Ok(StockPrice {
-        symbol: resp.quote.symbol,
-        price,
-        source: "alpha_vantage".to_string(),
-        timestamp: Utc::now().timestamp(),
-    })
+async fn print_migration_status(store: &dyn PriceStore) -> Result<(), StoreError> {
+    let migrations = store.migration_status().await?;
+    if migrations.is_empty() {
+        println!("The {} backend has no migrations.", store.backend());
+        return Ok(());
+    }
+    for m in migrations {
+        let state = if m.applied { "applied" } else { "pending" };
+        println!("{:>4} {:<8} {}", m.version, state, m.description);
+    }
+    Ok(())
 }
 
-// --- Save to DB ---
+async fn print_latest(store: &dyn PriceStore) -> Result<(), StoreError> {
+    let rows = store.latest_prices().await?;
+    if rows.is_empty() {
+        println!("No prices stored yet.");
+        return Ok(());
+    }
 
-async fn save_price(pool: &PgPool, p: &StockPrice) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"
-        INSERT INTO stock_prices (symbol, price, source, timestamp)
-        VALUES ($1, $2, $3, $4)
-        "#
-    )
-    .bind(&p.symbol)
-    .bind(p.price)
-    .bind(&p.source)
-    .bind(p.timestamp)
-    .execute(pool)
-    .await?;
+    println!(
+        "{:<10} {:>14} {:<4} {:>9} {:<15} {:<25} FETCHED",
+        "SYMBOL", "PRICE", "CCY", "CHANGE", "SOURCE", "QUOTED"
+    );
+    for row in rows {
+        let change = row
+            .change_percent
+            .map(|p| format!("{:+}%", p.round_dp(2)))
+            .unwrap_or_default();
+        println!(
+            "{:<10} {:>14} {:<4} {:>9} {:<15} {:<25} {}",
+            row.symbol,
+            row.price.to_string(),
+            row.currency,
+            change,
+            row.source,
+            row.quoted_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            row.fetched_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+    }
+    Ok(())
+}
 
+async fn aggregate(store: Arc<dyn PriceStore>, config: &Config) -> Result<(), StoreError> {
+    let report = Aggregator::new(store, &config.candles.resolutions, config.retention.raw()).run_once().await?;
+    println!("Aggregated {report}");
+    Ok(())
+}
+
+async fn prune(store: Arc<dyn PriceStore>, config: &Config) -> Result<(), StoreError> {
+    let report = Pruner::new(store, config.retention.clone(), config.candles.enabled)
+        .run_once()
+        .await?;
+    println!("Pruned {report}");
+    Ok(())
+}
+
+async fn print_candles(
+    store: &dyn PriceStore,
+    symbol: &str,
+    resolution: Resolution,
+    limit: usize,
+) -> Result<(), StoreError> {
+    let mut candles = store.candles(symbol, resolution, limit).await?;
+    if candles.is_empty() {
+        println!("No {resolution} candles for {symbol} yet.");
+        return Ok(());
+    }
+    candles.reverse();
+
+    println!(
+        "{:<25} {:>14} {:>14} {:>14} {:>14} {:>12} TICKS",
+        "START", "OPEN", "HIGH", "LOW", "CLOSE", "VOLUME"
+    );
+    for c in candles {
+        println!(
+            "{:<25} {:>14} {:>14} {:>14} {:>14} {:>12} {}",
+            c.bucket_start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            c.open.to_string(),
+            c.high.to_string(),
+            c.low.to_string(),
+            c.close.to_string(),
+            c.volume.map(|v| v.to_string()).unwrap_or_default(),
+            c.tick_count
+        );
+    }
     Ok(())
 }
 
 // --- Main ---
 
+/// Builds the configured sources. Only the commands that fetch need them,
+/// so missing API keys are reported here rather than by `Config::load`.
+fn build_providers(config: &Config) -> Result<Vec<Provider>, ConfigError> {
+    let client = config
+        .http
+        .build_client()
+        .map_err(|e| ConfigError::Invalid(format!("cannot build the HTTP client: {e}")))?;
+    Ok(sources::build_sources(config, &client)?
+        .into_iter()
+        .map(|source| {
+            let provider_config = config.provider(source.name());
+            Provider::new(source, &provider_config)
+        })
+        .collect())
+}
+
+/// Checks the command's arguments and builds the sources it fetches from,
+/// so mistakes are reported before anything connects to the store.
+fn prepare(command: &mut Command, config: &Config) -> Result<Vec<Provider>, ConfigError> {
+    match command {
+        Command::Backfill { symbol, from, to } => {
+            *symbol = config::normalize_symbol(symbol).map_err(ConfigError::Invalid)?;
+            if from > to {
+                return Err(ConfigError::Invalid(format!("--from {from} is after --to {to}")));
+            }
+            build_providers(config)
+        }
+        Command::Fetch { .. } | Command::Run => build_providers(config),
+        _ => Ok(Vec::new()),
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Stock price aggregator")]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Defaults to `run`.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fetch prices from every configured source
+    Fetch {
+        /// Run a single fetch cycle and exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Fetch periodically until Ctrl+C
+    Run,
+    /// Load daily history for a symbol
+    Backfill {
+        /// Symbol to load, e.g. AAPL
+        symbol: String,
+        /// First day to load (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day to load (YYYY-MM-DD)
+        #[arg(long, default_value_t = Utc::now().date_naive())]
+        to: NaiveDate,
+    },
+    /// Print the newest stored price per symbol
+    Latest,
+    /// Roll new prices up into candles once
+    Aggregate,
+    /// Delete prices and candles older than their retention once
+    Prune,
+    /// Print a symbol's newest candles
+    Candles {
+        /// Symbol to show, e.g. AAPL
+        symbol: String,
+        /// Candle width: 1m, 5m, 1h or 1d
+        #[arg(long, default_value = "1m")]
+        resolution: Resolution,
+        /// Number of candles to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show migration status and apply pending migrations
+    Migrate {
+        /// Only show the status, don't apply anything
+        #[arg(long)]
+        status: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
 
     tracing_subscriber::fmt()
-        .with_env_filter("info")
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
         .init();
 
+    let cli = Cli::parse();
+    let mut config = match Config::load(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut command = cli.command.unwrap_or(Command::Run);
+    let providers = match prepare(&mut command, &config) {
+        Ok(providers) => providers,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(2);
+        }
+    };
+
     info!("Starting TD1...");
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&std::env::var("DATABASE_URL")?)
-        .await?;
+    // Backfilled prices are history, not live quotes: keep them off the
+    // notify channel so TD2 doesn't stream them.
+    if matches!(command, Command::Backfill { .. }) {
+        config.database.notify_channel = None;
+    }
 
-    let symbols = ["AAPL", "GOOGL", "MSFT"];
+    let store = store::connect(&config).await?;
+    info!("Using {} store", store.backend());
 
-    for sym in symbols {
-        match fetch_alpha_vantage(sym).await {
-            Ok(price) => {
-                info!("Fetched {sym}: ${}", price.price);
-                if let Err(e) = save_price(&pool, &price).await {
-                    error!("DB error: {e}");
-                }
-            }
-            Err(err) => error!("Fetch error: {err}"),
+    if config.database.auto_migrate && !matches!(command, Command::Migrate { .. }) {
+        store.migrate().await?;
+        info!("Database migrations up to date");
+    }
+
+    let spawn_writer = || PriceWriter::spawn(store.clone(), config.writer.clone(), config.spool.clone());
+
+    match command {
+        Command::Fetch { once: true } => {
+            let writer = spawn_writer();
+            fetch_once(writer.sender(), &providers, &config).await;
+            writer.close().await?;
         }
+        Command::Fetch { once: false } | Command::Run => {
+            let shutdown = scheduler::shutdown_signal();
+            let aggregator = config.candles.enabled.then(|| {
+                let aggregator = Aggregator::new(
+                    store.clone(),
+                    &config.candles.resolutions,
+                    config.retention.raw(),
+                );
+                let period = Duration::from_secs(config.candles.interval_secs);
+                tokio::spawn(aggregator.run(period, shutdown.clone()))
+            });
+            let pruner = config.retention.enabled.then(|| {
+                let pruner = Pruner::new(store.clone(), config.retention.clone(), config.candles.enabled);
+                let period = Duration::from_secs(config.retention.interval_secs);
+                tokio::spawn(pruner.run(period, shutdown.clone()))
+            });
 
-        sleep(Duration::from_millis(500)).await;
+            let writer = spawn_writer();
+            run(writer.sender(), &providers, &config, shutdown).await;
+            writer.close().await?;
+            for job in aggregator.into_iter().chain(pruner) {
+                job.await?;
+            }
+        }
+        Command::Backfill { symbol, from, to } => {
+            let writer = spawn_writer();
+            backfill(writer.sender(), &providers, &symbol, from, to).await;
+            writer.close().await?;
+        }
+        Command::Latest => print_latest(store.as_ref()).await?,
+        Command::Aggregate => aggregate(store.clone(), &config).await?,
+        Command::Prune => prune(store.clone(), &config).await?,
+        Command::Candles { symbol, resolution, limit } => {
+            print_candles(store.as_ref(), &symbol, resolution, limit).await?
+        }
+        Command::Migrate { status } => migrate(store.as_ref(), status).await?,
     }
 
+    info!("Closing database connections...");
+    store.close().await;
+    info!("Shutdown complete");
+
     Ok(())
 }
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// --- Models ---
+
+/// Decimal places kept for prices; matches the `NUMERIC(18, 6)` column.
+pub const PRICE_SCALE: u32 = 6;
+
+/// Currency of every quote we fetch (all sources quote US listings).
+pub const USD: &str = "USD";
+
+/// A price snapshot. Besides the last price, sources fill in as much of
+/// the day's quote as they report; the rest stays `None`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StockPrice {
+    pub symbol: String,
+    /// Exact price, at most [`PRICE_SCALE`] decimal places.
+    pub price: Decimal,
+    /// ISO 4217 code.
+    pub currency: String,
+    pub source: String,
+    /// When the provider says the quote is from.
+    pub quoted_at: DateTime<Utc>,
+    /// When we fetched it.
+    pub fetched_at: DateTime<Utc>,
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub volume: Option<i64>,
+    pub previous_close: Option<Decimal>,
+    /// `price - previous_close`.
+    pub change: Option<Decimal>,
+    /// Change relative to `previous_close`, in percent.
+    pub change_percent: Option<Decimal>,
+    /// Trading day the quote belongs to.
+    pub trading_day: Option<NaiveDate>,
+}
+
+/// Parses a provider's decimal string exactly, rounding to [`PRICE_SCALE`].
+pub fn parse_price(raw: &str) -> Result<Decimal, rust_decimal::Error> {
+    Decimal::from_str(raw.trim()).map(|d| d.round_dp(PRICE_SCALE))
+}
+
+impl StockPrice {
+    /// How old the quote was when we fetched it.
+    pub fn age(&self) -> TimeDelta {
+        self.fetched_at - self.quoted_at
+    }
+}
+
+/// Approximate US market close (16:00 New York, standard time) on `day`.
+/// Used as the quote time for daily bars and day-level quotes.
+pub fn market_close(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::from_hms_opt(21, 0, 0).unwrap()).and_utc()
+}
@@ -0,0 +1,113 @@
+use std::future::Future;
+
+use chrono::NaiveDate;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+use crate::circuit_breaker::{BreakerState, CircuitBreaker};
+use crate::config::ProviderConfig;
+use crate::error::FetchError;
+use crate::models::StockPrice;
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::sources::PriceSource;
+
+/// A price source together with the per-source policies wrapped around it.
+pub struct Provider {
+    source: Box<dyn PriceSource>,
+    limiter: RateLimiter,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+}
+
+impl Provider {
+    pub fn new(source: Box<dyn PriceSource>, config: &ProviderConfig) -> Self {
+        let limiter = RateLimiter::new(source.name(), config.quota(source.name()));
+        let breaker = CircuitBreaker::new(source.name(), config.breaker);
+        Self {
+            source,
+            limiter,
+            retry: config.retry,
+            breaker,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    /// Fetches `symbol`, or returns `Ok(None)` when the circuit is open or
+    /// the quota says to skip it.
+    pub async fn fetch(&self, symbol: &str) -> Result<Option<StockPrice>, FetchError> {
+        self.call(symbol, || self.source.fetch(symbol)).await
+    }
+
+    /// Fetches daily prices for `symbol` between `from` and `to` (inclusive),
+    /// under the same quota, retry and circuit breaker policies as [`fetch`](Self::fetch).
+    pub async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<Vec<StockPrice>>, FetchError> {
+        self.call(symbol, || self.source.fetch_history(symbol, from, to)).await
+    }
+
+    async fn call<T, F, Fut>(&self, symbol: &str, request: F) -> Result<Option<T>, FetchError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        if !self.breaker.allow() {
+            debug!(source = self.name(), symbol, "Circuit open, skipping");
+            return Ok(None);
+        }
+
+        let result = self.call_with_retry(symbol, request).await;
+        match &result {
+            Ok(Some(_)) => self.breaker.record_success(),
+            Ok(None) | Err(FetchError::Unsupported(_)) => self.breaker.release(),
+            // The provider answered; it just doesn't know this symbol.
+            Err(FetchError::UnknownSymbol(_)) => self.breaker.record_success(),
+            Err(_) => self.breaker.record_failure(),
+        }
+        result
+    }
+
+    /// Transient errors are retried according to the source's retry policy.
+    async fn call_with_retry<T, F, Fut>(&self, symbol: &str, request: F) -> Result<Option<T>, FetchError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            if !self.limiter.acquire().await {
+                return Ok(None);
+            }
+
+            debug!(source = self.name(), symbol, attempt, "Fetching");
+            match request().await {
+                Ok(value) => return Ok(Some(value)),
+                Err(e) if e.is_transient() && attempt < self.retry.max_attempts => {
+                    let delay = self.retry.delay(attempt);
+                    warn!(
+                        source = self.name(),
+                        symbol,
+                        attempt,
+                        max_attempts = self.retry.max_attempts,
+                        ?delay,
+                        "Transient fetch error, retrying: {e}"
+                    );
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
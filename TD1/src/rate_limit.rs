@@ -0,0 +1,180 @@
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{debug, warn};
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Calls allowed per minute and per day. 0 means unlimited.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    pub per_minute: u32,
+    pub per_day: u32,
+}
+
+impl Quota {
+    /// Free-tier defaults for each provider.
+    pub fn default_for(source: &str) -> Self {
+        match source {
+            "alpha_vantage" => Self { per_minute: 5, per_day: 25 },
+            "finnhub" => Self { per_minute: 60, per_day: 0 },
+            _ => Self::default(),
+        }
+    }
+}
+
+// --- Token bucket ---
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+}
+
+impl Bucket {
+    fn new(capacity: u32, window: Duration) -> Self {
+        let capacity = capacity as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / window.as_secs_f64(),
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+    }
+
+    /// Time until one full token is available.
+    fn wait_time(&self) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens) / self.refill_per_sec).max(0.0))
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    minute: Option<Bucket>,
+    day: Option<Bucket>,
+    last_refill: Instant,
+}
+
+/// Per-source rate limiter with separate per-minute and per-day budgets.
+///
+/// Running out of the minute budget defers the call until a token is
+/// refilled; running out of the day budget skips it.
+#[derive(Debug)]
+pub struct RateLimiter {
+    source: String,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new(source: &str, quota: Quota) -> Self {
+        Self {
+            source: source.to_string(),
+            state: Mutex::new(State {
+                minute: (quota.per_minute > 0).then(|| Bucket::new(quota.per_minute, MINUTE)),
+                day: (quota.per_day > 0).then(|| Bucket::new(quota.per_day, DAY)),
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits for a call slot. Returns `false` if the daily budget is spent
+    /// and the call should be skipped.
+    pub async fn acquire(&self) -> bool {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now - state.last_refill;
+                state.last_refill = now;
+                if let Some(b) = state.minute.as_mut() {
+                    b.refill(elapsed);
+                }
+                if let Some(b) = state.day.as_mut() {
+                    b.refill(elapsed);
+                }
+
+                if let Some(day) = &state.day {
+                    if day.tokens < 1.0 {
+                        warn!(
+                            source = %self.source,
+                            retry_in = ?day.wait_time(),
+                            "Daily quota exhausted, skipping request"
+                        );
+                        return false;
+                    }
+                }
+
+                match &state.minute {
+                    Some(minute) if minute.tokens < 1.0 => minute.wait_time(),
+                    _ => {
+                        if let Some(b) = state.minute.as_mut() {
+                            b.tokens -= 1.0;
+                        }
+                        if let Some(b) = state.day.as_mut() {
+                            b.tokens -= 1.0;
+                        }
+                        debug!(
+                            source = %self.source,
+                            minute_left = state.minute.as_ref().map(|b| b.tokens.floor() as u32),
+                            day_left = state.day.as_ref().map(|b| b.tokens.floor() as u32),
+                            "Quota remaining"
+                        );
+                        return true;
+                    }
+                }
+            };
+
+            debug!(source = %self.source, ?wait, "Minute quota exhausted, deferring request");
+            sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_at_its_rate_up_to_capacity() {
+        let mut bucket = Bucket::new(60, MINUTE);
+        bucket.tokens = 0.0;
+        bucket.refill(Duration::from_millis(500));
+        assert_eq!(bucket.tokens, 0.5);
+        assert_eq!(bucket.wait_time(), Duration::from_millis(500));
+
+        bucket.refill(DAY);
+        assert_eq!(bucket.tokens, 60.0);
+        assert_eq!(bucket.wait_time(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn spent_day_quota_skips_calls() {
+        let limiter = RateLimiter::new("test", Quota { per_minute: 0, per_day: 2 });
+        assert!(limiter.acquire().await);
+        assert!(limiter.acquire().await);
+        assert!(!limiter.acquire().await);
+    }
+
+    #[tokio::test]
+    async fn spent_minute_quota_defers_calls() {
+        let limiter = RateLimiter::new("test", Quota { per_minute: 1, per_day: 0 });
+        assert!(limiter.acquire().await);
+        let second = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(second.is_err(), "second call should wait for the minute budget");
+    }
+
+    #[tokio::test]
+    async fn no_quota_means_no_limit() {
+        let limiter = RateLimiter::new("mock", Quota::default_for("mock"));
+        for _ in 0..1_000 {
+            assert!(limiter.acquire().await);
+        }
+    }
+}
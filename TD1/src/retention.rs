@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{TimeDelta, Utc};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio::time::{Duration, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::candles::Resolution;
+use crate::scheduler::Scheduler;
+use crate::store::{PriceStore, StoreError};
+
+/// How long to keep raw prices and each candle resolution. A retention of
+/// 0 days, or a resolution left out of `candles`, keeps data forever.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Run the pruner alongside `run`.
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub raw_days: u32,
+    pub candles: BTreeMap<Resolution, u32>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3_600,
+            raw_days: 7,
+            candles: BTreeMap::from([(Resolution::OneMinute, 90)]),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// How long raw prices are kept. This holds whether they are pruned by
+    /// the job alongside `run` or by the `prune` command, so the aggregator
+    /// goes by it even when `enabled` is off.
+    pub fn raw(&self) -> Option<TimeDelta> {
+        days(self.raw_days)
+    }
+
+    pub fn candles(&self, resolution: Resolution) -> Option<TimeDelta> {
+        self.candles.get(&resolution).copied().and_then(days)
+    }
+}
+
+fn days(n: u32) -> Option<TimeDelta> {
+    (n > 0).then(|| TimeDelta::days(n.into()))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PruneReport {
+    pub prices: u64,
+    pub candles: BTreeMap<Resolution, u64>,
+}
+
+impl fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} raw prices", self.prices)?;
+        for (resolution, n) in &self.candles {
+            write!(f, ", {n} {resolution} candles")?;
+        }
+        Ok(())
+    }
+}
+
+/// Deletes data that has outlived its retention.
+pub struct Pruner {
+    store: Arc<dyn PriceStore>,
+    config: RetentionConfig,
+    /// Whether the candle aggregator runs. If it does, raw prices are only
+    /// pruned once they have been rolled up into candles.
+    aggregating: bool,
+}
+
+impl Pruner {
+    pub fn new(store: Arc<dyn PriceStore>, config: RetentionConfig, aggregating: bool) -> Self {
+        Self { store, config, aggregating }
+    }
+
+    pub async fn run_once(&self) -> Result<PruneReport, StoreError> {
+        let now = Utc::now();
+        let mut report = PruneReport::default();
+
+        if let Some(keep) = self.config.raw() {
+            report.prices = self.store.prune_prices(now - keep, self.aggregating).await?;
+        }
+
+        for resolution in Resolution::ALL {
+            if let Some(keep) = self.config.candles(resolution) {
+                let removed = self.store.prune_candles(resolution, now - keep).await?;
+                report.candles.insert(resolution, removed);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Prunes every `period` until shutdown.
+    pub async fn run(self, period: Duration, shutdown: watch::Receiver<bool>) {
+        let scheduler = Scheduler::new("Pruning old data", period, MissedTickBehavior::Skip);
+        scheduler
+            .run(shutdown, || async {
+                match self.run_once().await {
+                    Ok(report) => info!("Pruned {report}"),
+                    Err(e) => error!("Pruning failed: {e}"),
+                }
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::candles::Aggregator;
+    use crate::models::{StockPrice, USD};
+    use crate::store::MemoryStore;
+
+    fn price(quoted_at: chrono::DateTime<Utc>) -> StockPrice {
+        StockPrice {
+            symbol: "AAPL".to_string(),
+            price: Decimal::new(100, 0),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            quoted_at,
+            fetched_at: Utc::now(),
+            ..StockPrice::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_prices_are_kept_until_they_are_aggregated() {
+        let store = Arc::new(MemoryStore::new());
+        let old = Utc.with_ymd_and_hms(2026, 9, 1, 14, 0, 0).unwrap();
+        store.save_prices(&[price(old), price(Utc::now())]).await.unwrap();
+
+        let config = RetentionConfig {
+            raw_days: 1,
+            candles: BTreeMap::from([(Resolution::OneMinute, 1)]),
+            ..RetentionConfig::default()
+        };
+        let pruner = Pruner::new(store.clone(), config.clone(), true);
+        assert_eq!(pruner.run_once().await.unwrap().prices, 0);
+
+        Aggregator::new(store.clone(), &Resolution::ALL, config.raw()).run_once().await.unwrap();
+        let report = pruner.run_once().await.unwrap();
+        assert_eq!(report.prices, 1);
+        assert_eq!(report.candles[&Resolution::OneMinute], 1);
+        assert_eq!(store.candles("AAPL", Resolution::OneDay, 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pruning_by_hand_does_not_shrink_candles() {
+        let store = Arc::new(MemoryStore::new());
+        let old = Utc.with_ymd_and_hms(2026, 9, 1, 14, 0, 0).unwrap();
+        store.save_prices(&[price(old)]).await.unwrap();
+
+        let config = RetentionConfig { enabled: false, raw_days: 1, ..RetentionConfig::default() };
+        let aggregator = Aggregator::new(store.clone(), &[Resolution::OneMinute], config.raw());
+        aggregator.run_once().await.unwrap();
+        let report = Pruner::new(store.clone(), config, true).run_once().await.unwrap();
+        assert_eq!(report.prices, 1);
+
+        store.save_prices(&[price(old + TimeDelta::seconds(30))]).await.unwrap();
+        aggregator.run_once().await.unwrap();
+        let candles = store.candles("AAPL", Resolution::OneMinute, 10).await.unwrap();
+        assert_eq!(candles[0].tick_count, 2);
+    }
+
+    #[tokio::test]
+    async fn zero_days_keeps_data_forever() {
+        let store = Arc::new(MemoryStore::new());
+        store.save_prices(&[price(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap())]).await.unwrap();
+
+        let config = RetentionConfig { raw_days: 0, candles: BTreeMap::new(), ..RetentionConfig::default() };
+        let report = Pruner::new(store, config, false).run_once().await.unwrap();
+        assert_eq!(report.prices, 0);
+        assert!(report.candles.is_empty());
+    }
+}
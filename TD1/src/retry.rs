@@ -0,0 +1,67 @@
+use rand::Rng;
+use serde::Deserialize;
+use tokio::time::Duration;
+
+/// Exponential backoff with jitter for transient fetch failures.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Fraction of each delay (0.0..=1.0) that is randomised away.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait after the given failed attempt (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = Duration::from_millis(self.base_delay_ms)
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(Duration::from_millis(self.max_delay_ms));
+
+        let factor = 1.0 - self.jitter * rand::thread_rng().gen::<f64>();
+        exp.mul_f64(factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter,
+        }
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let delays: Vec<u128> = (1..=6).map(|attempt| policy(0.0).delay(attempt).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(policy(0.0).delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_only_shortens_the_delay() {
+        for _ in 0..100 {
+            let delay = policy(0.5).delay(2);
+            assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+}
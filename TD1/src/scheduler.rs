@@ -0,0 +1,57 @@
+use std::future::Future;
+
+use tokio::sync::watch;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::info;
+
+/// Runs a job on a fixed period until shutdown is signalled.
+pub struct Scheduler {
+    name: &'static str,
+    period: Duration,
+    missed_tick: MissedTickBehavior,
+}
+
+impl Scheduler {
+    /// `name` describes the job in logs, e.g. "Fetching prices".
+    pub fn new(name: &'static str, period: Duration, missed_tick: MissedTickBehavior) -> Self {
+        Self { name, period, missed_tick }
+    }
+
+    /// Calls `cycle` on every tick. A cycle that is already running when
+    /// shutdown arrives is awaited to completion before returning.
+    pub async fn run<F, Fut>(&self, mut shutdown: watch::Receiver<bool>, mut cycle: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut ticker = interval(self.period);
+        ticker.set_missed_tick_behavior(self.missed_tick);
+
+        info!("{} every {:?} (missed ticks: {:?})", self.name, self.period, self.missed_tick);
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            tokio::select! {
+                _ = ticker.tick() => cycle().await,
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+}
+
+/// Returns a receiver that flips to `true` once Ctrl+C is pressed.
+pub fn shutdown_signal() -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Shutdown signal received, finishing in-flight work...");
+        }
+        let _ = tx.send(true);
+    });
+
+    rx
+}
@@ -0,0 +1,629 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::config::{Config, ConfigError};
+use crate::error::{check_status, FetchError};
+use crate::models::{market_close, parse_price, StockPrice, USD};
+
+pub type FetchResult = Result<StockPrice, FetchError>;
+
+/// A provider that can return the current price of a symbol.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Identifier stored in the `source` column.
+    fn name(&self) -> &str;
+
+    async fn fetch(&self, symbol: &str) -> FetchResult;
+
+    /// Daily closing prices between `from` and `to` (inclusive), oldest first.
+    async fn fetch_history(
+        &self,
+        _symbol: &str,
+        _from: NaiveDate,
+        _to: NaiveDate,
+    ) -> Result<Vec<StockPrice>, FetchError> {
+        Err(FetchError::Unsupported("history"))
+    }
+}
+
+/// Builds the sources listed in `config.sources`. HTTP sources share `client`.
+pub fn build_sources(config: &Config, client: &Client) -> Result<Vec<Box<dyn PriceSource>>, ConfigError> {
+    config
+        .sources
+        .iter()
+        .map(|name| -> Result<Box<dyn PriceSource>, ConfigError> {
+            Ok(match name.as_str() {
+                "alpha_vantage" => Box::new(AlphaVantage::new(client.clone(), config.api_key(name)?)),
+                "finnhub" => Box::new(Finnhub::new(client.clone(), config.api_key(name)?)),
+                "mock" => Box::new(MockSource),
+                other => return Err(ConfigError::Invalid(format!("unknown source {other:?}"))),
+            })
+        })
+        .collect()
+}
+
+// --- Alpha Vantage ---
+
+/// Alpha Vantage answers HTTP 200 for throttling and bad requests, with the
+/// reason in one of these fields instead of a quote.
+#[derive(Deserialize, Debug)]
+struct GlobalQuote {
+    #[serde(rename = "Global Quote")]
+    quote: Option<Quote>,
+    #[serde(rename = "Note")]
+    note: Option<String>,
+    #[serde(rename = "Information")]
+    information: Option<String>,
+    #[serde(rename = "Error Message")]
+    error_message: Option<String>,
+}
+
+/// Every field is optional because an unknown symbol comes back as an
+/// empty `"Global Quote": {}`.
+#[derive(Deserialize, Debug)]
+struct Quote {
+    #[serde(rename = "01. symbol")]
+    symbol: Option<String>,
+    #[serde(rename = "02. open")]
+    open: Option<String>,
+    #[serde(rename = "03. high")]
+    high: Option<String>,
+    #[serde(rename = "04. low")]
+    low: Option<String>,
+    #[serde(rename = "05. price")]
+    price: Option<String>,
+    #[serde(rename = "06. volume")]
+    volume: Option<String>,
+    #[serde(rename = "07. latest trading day")]
+    latest_trading_day: Option<String>,
+    #[serde(rename = "08. previous close")]
+    previous_close: Option<String>,
+    #[serde(rename = "09. change")]
+    change: Option<String>,
+    /// Formatted like `"1.2345%"`.
+    #[serde(rename = "10. change percent")]
+    change_percent: Option<String>,
+}
+
+impl Quote {
+    /// Alpha Vantage only reports the trading day, not a quote time. While
+    /// that day's session is open the quote is taken as current; after the
+    /// close it is stamped with the close, so repeated fetches of a finished
+    /// day collapse into one row.
+    fn into_stock_price(self, source: &str) -> Result<StockPrice, FetchError> {
+        let (Some(symbol), Some(price)) = (self.symbol, self.price) else {
+            return Err(FetchError::Malformed("incomplete \"Global Quote\"".to_string()));
+        };
+
+        let fetched_at = Utc::now();
+        let trading_day: Option<NaiveDate> = opt_field("07. latest trading day", self.latest_trading_day)?;
+        let quoted_at = trading_day.map_or(fetched_at, |day| market_close(day).min(fetched_at));
+
+        Ok(StockPrice {
+            symbol,
+            price: price_field("05. price", &price)?,
+            currency: USD.to_string(),
+            source: source.to_string(),
+            quoted_at,
+            fetched_at,
+            open: opt_price_field("02. open", self.open)?,
+            high: opt_price_field("03. high", self.high)?,
+            low: opt_price_field("04. low", self.low)?,
+            volume: opt_field("06. volume", self.volume)?,
+            previous_close: opt_price_field("08. previous close", self.previous_close)?,
+            change: opt_price_field("09. change", self.change)?,
+            change_percent: opt_price_field(
+                "10. change percent",
+                self.change_percent.map(|p| p.trim_end_matches('%').to_string()),
+            )?,
+            trading_day,
+        })
+    }
+}
+
+fn price_field(name: &str, raw: &str) -> Result<Decimal, FetchError> {
+    parse_price(raw).map_err(|e| FetchError::Malformed(format!("{name} {raw:?}: {e}")))
+}
+
+fn opt_price_field(name: &str, raw: Option<String>) -> Result<Option<Decimal>, FetchError> {
+    raw.map(|raw| price_field(name, &raw)).transpose()
+}
+
+fn opt_field<T>(name: &str, raw: Option<String>) -> Result<Option<T>, FetchError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    raw.map(|raw| {
+        raw.trim()
+            .parse::<T>()
+            .map_err(|e| FetchError::Malformed(format!("{name} {raw:?}: {e}")))
+    })
+    .transpose()
+}
+
+/// Throttle notices can mention the API key too ("We have detected your API
+/// key as ... and our standard API rate limit is 25 requests per day"), so
+/// these phrases are checked before the key is.
+const THROTTLE_PHRASES: [&str; 4] = ["rate limit", "call frequency", "per day", "per minute"];
+
+fn mentions_throttle(msg: &str) -> bool {
+    let msg = msg.to_lowercase();
+    THROTTLE_PHRASES.iter().any(|phrase| msg.contains(phrase))
+}
+
+fn mentions_api_key(msg: &str) -> bool {
+    let msg = msg.to_lowercase();
+    msg.contains("apikey") || msg.contains("api key")
+}
+
+impl GlobalQuote {
+    /// Turns a throttle, auth or error notice into the matching `FetchError`.
+    fn check_notices(&self, symbol: &str) -> Result<(), FetchError> {
+        if let Some(msg) = self.error_message.clone() {
+            return Err(if mentions_throttle(&msg) {
+                FetchError::RateLimited(msg)
+            } else if mentions_api_key(&msg) {
+                FetchError::Auth(msg)
+            } else {
+                FetchError::UnknownSymbol(symbol.to_string())
+            });
+        }
+        if let Some(msg) = self.information.clone() {
+            return Err(if mentions_api_key(&msg) && !mentions_throttle(&msg) {
+                FetchError::Auth(msg)
+            } else {
+                FetchError::RateLimited(msg)
+            });
+        }
+        if let Some(msg) = self.note.clone() {
+            return Err(FetchError::RateLimited(msg));
+        }
+        Ok(())
+    }
+
+    fn into_quote(self, symbol: &str) -> Result<Quote, FetchError> {
+        self.check_notices(symbol)?;
+
+        match self.quote {
+            Some(Quote { symbol: None, price: None, .. }) => {
+                Err(FetchError::UnknownSymbol(symbol.to_string()))
+            }
+            Some(quote) => Ok(quote),
+            None => Err(FetchError::Malformed("missing \"Global Quote\"".to_string())),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct DailySeries {
+    #[serde(rename = "Time Series (Daily)")]
+    series: Option<BTreeMap<NaiveDate, DailyBar>>,
+    #[serde(rename = "Note")]
+    note: Option<String>,
+    #[serde(rename = "Information")]
+    information: Option<String>,
+    #[serde(rename = "Error Message")]
+    error_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DailyBar {
+    #[serde(rename = "1. open")]
+    open: String,
+    #[serde(rename = "2. high")]
+    high: String,
+    #[serde(rename = "3. low")]
+    low: String,
+    #[serde(rename = "4. close")]
+    close: String,
+    #[serde(rename = "5. volume")]
+    volume: String,
+}
+
+impl DailySeries {
+    fn into_series(self, symbol: &str) -> Result<BTreeMap<NaiveDate, DailyBar>, FetchError> {
+        GlobalQuote {
+            quote: None,
+            note: self.note,
+            information: self.information,
+            error_message: self.error_message,
+        }
+        .check_notices(symbol)?;
+
+        self.series
+            .ok_or_else(|| FetchError::Malformed("missing \"Time Series (Daily)\"".to_string()))
+    }
+}
+
+pub struct AlphaVantage {
+    client: Client,
+    api_key: String,
+}
+
+impl AlphaVantage {
+    pub fn new(client: Client, api_key: String) -> Self {
+        Self { client, api_key }
+    }
+}
+
+#[async_trait]
+impl PriceSource for AlphaVantage {
+    fn name(&self) -> &str {
+        "alpha_vantage"
+    }
+
+    async fn fetch(&self, symbol: &str) -> FetchResult {
+        let resp = self
+            .client
+            .get("https://www.alphavantage.co/query")
+            .query(&[("function", "GLOBAL_QUOTE"), ("symbol", symbol), ("apikey", &self.api_key)])
+            .send()
+            .await?;
+        let body = check_status(resp)?.text().await?;
+        serde_json::from_str::<GlobalQuote>(&body)?
+            .into_quote(symbol)?
+            .into_stock_price(self.name())
+    }
+
+    async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<StockPrice>, FetchError> {
+        // The compact series only covers the last 100 trading days.
+        let outputsize = if (Utc::now().date_naive() - from).num_days() > 140 {
+            "full"
+        } else {
+            "compact"
+        };
+        let resp = self
+            .client
+            .get("https://www.alphavantage.co/query")
+            .query(&[
+                ("function", "TIME_SERIES_DAILY"),
+                ("symbol", symbol),
+                ("outputsize", outputsize),
+                ("apikey", &self.api_key),
+            ])
+            .send()
+            .await?;
+        let body = check_status(resp)?.text().await?;
+        let series = serde_json::from_str::<DailySeries>(&body)?.into_series(symbol)?;
+
+        let fetched_at = Utc::now();
+        series
+            .range(from..=to)
+            .map(|(date, bar)| {
+                Ok(StockPrice {
+                    symbol: symbol.to_string(),
+                    price: price_field("4. close", &bar.close)?,
+                    currency: USD.to_string(),
+                    source: self.name().to_string(),
+                    // Today's bar is still open until the close.
+                    quoted_at: market_close(*date).min(fetched_at),
+                    fetched_at,
+                    open: Some(price_field("1. open", &bar.open)?),
+                    high: Some(price_field("2. high", &bar.high)?),
+                    low: Some(price_field("3. low", &bar.low)?),
+                    volume: opt_field("5. volume", Some(bar.volume.clone()))?,
+                    trading_day: Some(*date),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+// --- Finnhub ---
+
+#[derive(Deserialize, Debug)]
+struct FinnhubQuote {
+    /// Current price.
+    #[serde(deserialize_with = "de_decimal")]
+    c: Decimal,
+    /// Change.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    d: Option<Decimal>,
+    /// Percent change.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    dp: Option<Decimal>,
+    /// High of the day.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    h: Option<Decimal>,
+    /// Low of the day.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    l: Option<Decimal>,
+    /// Open of the day.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    o: Option<Decimal>,
+    /// Previous close.
+    #[serde(default, deserialize_with = "de_opt_decimal")]
+    pc: Option<Decimal>,
+    /// Quote time (Unix seconds); 0 when Finnhub doesn't know the symbol.
+    t: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct FinnhubCandles {
+    /// `ok` or `no_data`.
+    s: String,
+    /// Open prices.
+    #[serde(default, deserialize_with = "de_decimals")]
+    o: Vec<Decimal>,
+    /// High prices.
+    #[serde(default, deserialize_with = "de_decimals")]
+    h: Vec<Decimal>,
+    /// Low prices.
+    #[serde(default, deserialize_with = "de_decimals")]
+    l: Vec<Decimal>,
+    /// Close prices.
+    #[serde(default, deserialize_with = "de_decimals")]
+    c: Vec<Decimal>,
+    /// Volumes.
+    #[serde(default)]
+    v: Vec<f64>,
+    /// Bar timestamps.
+    #[serde(default)]
+    t: Vec<i64>,
+}
+
+/// Finnhub sends prices as JSON numbers. Formatting the parsed `f64` gives
+/// back the shortest string that round-trips, i.e. the digits Finnhub sent,
+/// which is then parsed exactly.
+fn number_to_price(n: f64) -> Result<Decimal, String> {
+    parse_price(&n.to_string()).map_err(|e| format!("price {n}: {e}"))
+}
+
+fn de_decimal<'de, D: Deserializer<'de>>(d: D) -> Result<Decimal, D::Error> {
+    number_to_price(f64::deserialize(d)?).map_err(de::Error::custom)
+}
+
+/// Finnhub sends `null` for fields it doesn't have.
+fn de_opt_decimal<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Decimal>, D::Error> {
+    Option::<f64>::deserialize(d)?
+        .map(number_to_price)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+fn de_decimals<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Decimal>, D::Error> {
+    Vec::<f64>::deserialize(d)?
+        .into_iter()
+        .map(number_to_price)
+        .collect::<Result<_, _>>()
+        .map_err(de::Error::custom)
+}
+
+pub struct Finnhub {
+    client: Client,
+    api_key: String,
+}
+
+impl Finnhub {
+    pub fn new(client: Client, api_key: String) -> Self {
+        Self { client, api_key }
+    }
+}
+
+#[async_trait]
+impl PriceSource for Finnhub {
+    fn name(&self) -> &str {
+        "finnhub"
+    }
+
+    async fn fetch(&self, symbol: &str) -> FetchResult {
+        let resp = self
+            .client
+            .get("https://finnhub.io/api/v1/quote")
+            .query(&[("symbol", symbol)])
+            .header("X-Finnhub-Token", &self.api_key)
+            .send()
+            .await?;
+        let resp = check_status(resp)?.json::<FinnhubQuote>().await?;
+
+        if resp.t == 0 {
+            return Err(FetchError::UnknownSymbol(symbol.to_string()));
+        }
+        let quoted_at = DateTime::from_timestamp(resp.t, 0)
+            .ok_or_else(|| FetchError::Malformed(format!("quote time {}", resp.t)))?;
+
+        Ok(StockPrice {
+            symbol: symbol.to_string(),
+            price: resp.c,
+            currency: USD.to_string(),
+            source: self.name().to_string(),
+            quoted_at,
+            fetched_at: Utc::now(),
+            open: resp.o,
+            high: resp.h,
+            low: resp.l,
+            previous_close: resp.pc,
+            change: resp.d,
+            change_percent: resp.dp,
+            trading_day: Some(quoted_at.date_naive()),
+            ..Default::default()
+        })
+    }
+
+    async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<StockPrice>, FetchError> {
+        let start = from.and_time(NaiveTime::MIN).and_utc().timestamp();
+        let end = (to + Days::new(1)).and_time(NaiveTime::MIN).and_utc().timestamp() - 1;
+
+        let resp = self
+            .client
+            .get("https://finnhub.io/api/v1/stock/candle")
+            .query(&[("symbol", symbol), ("resolution", "D")])
+            .query(&[("from", start), ("to", end)])
+            .header("X-Finnhub-Token", &self.api_key)
+            .send()
+            .await?;
+        let candles = check_status(resp)?.json::<FinnhubCandles>().await?;
+
+        match candles.s.as_str() {
+            "ok" => {}
+            "no_data" => return Ok(Vec::new()),
+            other => return Err(FetchError::Malformed(format!("candle status {other:?}"))),
+        }
+        let n = candles.t.len();
+        if [candles.o.len(), candles.h.len(), candles.l.len(), candles.c.len(), candles.v.len()]
+            .iter()
+            .any(|len| *len != n)
+        {
+            return Err(FetchError::Malformed("candle arrays differ in length".to_string()));
+        }
+
+        let fetched_at = Utc::now();
+        (0..n)
+            .map(|i| {
+                let quoted_at = DateTime::from_timestamp(candles.t[i], 0)
+                    .ok_or_else(|| FetchError::Malformed(format!("candle time {}", candles.t[i])))?;
+                Ok(StockPrice {
+                    symbol: symbol.to_string(),
+                    price: candles.c[i],
+                    currency: USD.to_string(),
+                    source: self.name().to_string(),
+                    quoted_at,
+                    fetched_at,
+                    open: Some(candles.o[i]),
+                    high: Some(candles.h[i]),
+                    low: Some(candles.l[i]),
+                    volume: Some(candles.v[i] as i64),
+                    trading_day: Some(quoted_at.date_naive()),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+// --- Mock ---
+
+/// Offline source: always returns the same price for a given symbol.
+pub struct MockSource;
+
+#[async_trait]
+impl PriceSource for MockSource {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn fetch(&self, symbol: &str) -> FetchResult {
+        let now = Utc::now();
+        Ok(mock_quote(symbol, self.name(), now.date_naive(), now))
+    }
+
+    async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<StockPrice>, FetchError> {
+        let now = Utc::now();
+        Ok(from
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .map(|date| mock_quote(symbol, self.name(), date, market_close(date).min(now)))
+            .collect())
+    }
+}
+
+/// Deterministic daily quote: the same symbol and day always give the same
+/// numbers.
+fn mock_quote(symbol: &str, source: &str, day: NaiveDate, quoted_at: DateTime<Utc>) -> StockPrice {
+    let salt = market_close(day).timestamp() as u64;
+    let price = mock_price(symbol, salt);
+    let open = mock_price(symbol, salt ^ 1);
+    let previous_close = mock_price(symbol, salt.wrapping_sub(86_400));
+    let change = price - previous_close;
+
+    StockPrice {
+        symbol: symbol.to_string(),
+        price,
+        currency: USD.to_string(),
+        source: source.to_string(),
+        quoted_at,
+        fetched_at: Utc::now(),
+        open: Some(open),
+        high: Some(price.max(open)),
+        low: Some(price.min(open)),
+        volume: Some(1_000 + (salt % 1_000_000) as i64),
+        previous_close: Some(previous_close),
+        change: Some(change),
+        change_percent: Some((change * Decimal::ONE_HUNDRED / previous_close).round_dp(4)),
+        trading_day: Some(day),
+    }
+}
+
+/// Deterministic price for the symbol, within 5% of a per-symbol base
+/// price depending on `salt`.
+fn mock_price(symbol: &str, salt: u64) -> Decimal {
+    let base_cents = symbol
+        .bytes()
+        .fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64))
+        % 50_000
+        + 5_000;
+    let noise = (salt.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(base_cents) >> 33) % 1_001;
+
+    Decimal::new((base_cents * (9_500 + noise) / 10_000) as i64, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(field: &str, msg: &str) -> FetchError {
+        let body = serde_json::json!({ field: msg });
+        match serde_json::from_value::<GlobalQuote>(body).unwrap().into_quote("AAPL") {
+            Ok(_) => panic!("{field} notice parsed as a quote"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn key_notices_are_auth_errors() {
+        let invalid = "the parameter apikey is invalid or missing. Please claim your free API key.";
+        assert!(matches!(classify("Error Message", invalid), FetchError::Auth(_)));
+        assert!(matches!(classify("Information", invalid), FetchError::Auth(_)));
+    }
+
+    #[test]
+    fn throttle_notices_are_rate_limits_even_when_they_mention_the_key() {
+        let daily = "We have detected your API key as demo and our standard API rate limit is 25 requests per day.";
+        assert!(matches!(classify("Information", daily), FetchError::RateLimited(_)));
+        assert!(matches!(classify("Error Message", daily), FetchError::RateLimited(_)));
+
+        let frequency = "Thank you for using Alpha Vantage! Our standard API call frequency is 5 calls per minute.";
+        assert!(matches!(classify("Note", frequency), FetchError::RateLimited(_)));
+        assert!(matches!(classify("Information", frequency), FetchError::RateLimited(_)));
+    }
+
+    #[test]
+    fn other_errors_and_empty_quotes_mean_an_unknown_symbol() {
+        let invalid = "Invalid API call. Please retry or visit the documentation for GLOBAL_QUOTE.";
+        assert!(matches!(classify("Error Message", invalid), FetchError::UnknownSymbol(s) if s == "AAPL"));
+
+        let empty: GlobalQuote = serde_json::from_str(r#"{"Global Quote": {}}"#).unwrap();
+        assert!(matches!(empty.into_quote("AAPL"), Err(FetchError::UnknownSymbol(_))));
+    }
+
+    #[tokio::test]
+    async fn backfilled_bars_are_never_quoted_after_they_were_fetched() {
+        let today = Utc::now().date_naive();
+        let bars = MockSource.fetch_history("AAPL", today - Days::new(2), today).await.unwrap();
+        assert_eq!(bars.len(), 3);
+        assert!(bars.iter().all(|bar| bar.quoted_at <= bar.fetched_at));
+    }
+}
@@ -0,0 +1,326 @@
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::models::StockPrice;
+use crate::store::{self, PriceStore, SaveReport};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpoolConfig {
+    pub enabled: bool,
+    /// Append-only file, one JSON price per line. Prices the store refuses
+    /// outright go to a `.rejected.jsonl` file next to it.
+    pub path: PathBuf,
+    /// Prices that don't fit are dropped.
+    pub max_bytes: u64,
+    /// Wait this long after a failed write before trying the store again.
+    pub retry_secs: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("td1.spool.jsonl"),
+            max_bytes: 64 * 1024 * 1024,
+            retry_secs: 30,
+        }
+    }
+}
+
+/// Holds prices the store couldn't take until it is reachable again.
+/// Entries survive restarts and are replayed in the order they were
+/// spooled. Several TD1 processes may share the file: each change to it
+/// happens under an exclusive lock on a `.lock` file next to it.
+pub struct Spool {
+    config: SpoolConfig,
+    bytes: u64,
+    pending: usize,
+    last_failure: Option<Instant>,
+}
+
+impl Spool {
+    /// Opens the spool, picking up prices left over from a previous run.
+    pub async fn open(config: SpoolConfig) -> io::Result<Self> {
+        let (bytes, pending) = match fs::read_to_string(&config.path).await {
+            Ok(text) => (text.len() as u64, text.lines().count()),
+            Err(e) if e.kind() == ErrorKind::NotFound => (0, 0),
+            Err(e) => return Err(e),
+        };
+
+        let spool = Self { config, bytes, pending, last_failure: None };
+        if pending > 0 {
+            warn!("Found prices from a previous run; {}", spool.state());
+        }
+        Ok(spool)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes == 0
+    }
+
+    /// Waits for the exclusive lock on the spool. It is released when the
+    /// returned file is dropped.
+    async fn lock(&self) -> io::Result<std::fs::File> {
+        let path = self.config.path.with_extension("lock");
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+            file.lock()?;
+            Ok(file)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    /// Re-reads the file size, which other processes may have changed.
+    async fn refresh_size(&mut self) -> io::Result<()> {
+        self.bytes = match fs::metadata(&self.config.path).await {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        if self.bytes == 0 {
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    /// Whether enough time has passed since the last failure to try the
+    /// store again.
+    pub fn retry_due(&self) -> bool {
+        self.last_failure
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(self.config.retry_secs))
+    }
+
+    /// Appends `prices` after a failed write. Prices that would push the
+    /// file past `max_bytes` are dropped and counted as failed.
+    pub async fn append(&mut self, prices: &[StockPrice]) -> SaveReport {
+        self.last_failure = Some(Instant::now());
+
+        let lock = match self.lock().await {
+            Ok(lock) => lock,
+            Err(e) => {
+                error!("Cannot lock spool {}: {e}; dropping {} prices", self.config.path.display(), prices.len());
+                return SaveReport { failed: prices.len(), ..Default::default() };
+            }
+        };
+        if let Err(e) = self.refresh_size().await {
+            error!("Cannot read spool {}: {e}; dropping {} prices", self.config.path.display(), prices.len());
+            return SaveReport { failed: prices.len(), ..Default::default() };
+        }
+
+        let mut buf = Vec::new();
+        let mut spooled = 0;
+        for price in prices {
+            let mut line = serde_json::to_vec(price).expect("StockPrice serializes to JSON");
+            line.push(b'\n');
+            if self.bytes + (buf.len() + line.len()) as u64 > self.config.max_bytes {
+                break;
+            }
+            buf.extend_from_slice(&line);
+            spooled += 1;
+        }
+
+        if let Err(e) = append_to(&self.config.path, &buf).await {
+            error!("Cannot write spool {}: {e}; dropping {} prices", self.config.path.display(), prices.len());
+            return SaveReport { failed: prices.len(), ..Default::default() };
+        }
+        drop(lock);
+        self.bytes += buf.len() as u64;
+        self.pending += spooled;
+
+        let dropped = prices.len() - spooled;
+        if dropped > 0 {
+            error!(
+                "Spool {} is full ({} bytes), dropping {dropped} prices",
+                self.config.path.display(),
+                self.config.max_bytes
+            );
+        }
+        warn!("Spooled {spooled} prices; {}", self.state());
+        SaveReport { spooled, failed: dropped, ..Default::default() }
+    }
+
+    /// Moves prices the store refused on their own to the rejects file, so
+    /// they don't hold up the prices behind them. They are counted as
+    /// rejected even if the file can't be written.
+    pub async fn quarantine(&self, prices: &[StockPrice]) -> SaveReport {
+        if prices.is_empty() {
+            return SaveReport::default();
+        }
+
+        let path = self.rejects_path();
+        let mut buf = Vec::new();
+        for price in prices {
+            serde_json::to_writer(&mut buf, price).expect("StockPrice serializes to JSON");
+            buf.push(b'\n');
+        }
+        match append_to(&path, &buf).await {
+            Ok(()) => error!("Moved {} rejected prices to {}", prices.len(), path.display()),
+            Err(e) => error!("Cannot write {}: {e}; dropping {} rejected prices", path.display(), prices.len()),
+        }
+        SaveReport { rejected: prices.len(), ..Default::default() }
+    }
+
+    fn rejects_path(&self) -> PathBuf {
+        self.config.path.with_extension("rejected.jsonl")
+    }
+
+    /// Writes spooled prices to `store` in order, `batch_size` at a time.
+    /// Rows the store refuses on their own are quarantined. Stops at any
+    /// other error and keeps what is left for the next try.
+    pub async fn replay(&mut self, store: &dyn PriceStore, batch_size: usize) -> Result<SaveReport, io::Error> {
+        // Held until the file is rewritten, so nothing another process
+        // appends in the meantime is lost.
+        let _lock = self.lock().await?;
+        let text = match fs::read_to_string(&self.config.path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let lines = text.lines().count();
+        let mut prices = Vec::with_capacity(self.pending);
+        for (n, line) in text.lines().enumerate() {
+            match serde_json::from_str::<StockPrice>(line) {
+                Ok(price) => prices.push(price),
+                Err(e) => warn!("Skipping corrupt spool line {}: {e}", n + 1),
+            }
+        }
+
+        let mut report = SaveReport::default();
+        let mut written = 0;
+        for chunk in prices.chunks(batch_size) {
+            match store::save_isolating(store, chunk).await {
+                Ok((r, rejected)) => {
+                    report += r;
+                    report += self.quarantine(&rejected).await;
+                    written += chunk.len();
+                }
+                Err(e) => {
+                    error!("Store still unavailable, keeping spooled prices: {e}");
+                    self.last_failure = Some(Instant::now());
+                    break;
+                }
+            }
+        }
+
+        if written > 0 || prices.len() != lines {
+            self.rewrite(&prices[written..]).await?;
+        } else {
+            self.bytes = text.len() as u64;
+            self.pending = lines;
+        }
+        if written > 0 {
+            info!("Replayed {written} spooled prices ({report})");
+        }
+        if self.pending > 0 {
+            warn!("{}", self.state());
+        }
+        Ok(report)
+    }
+
+    /// Replaces the file with `rest`, or removes it when nothing is left.
+    async fn rewrite(&mut self, rest: &[StockPrice]) -> io::Result<()> {
+        if rest.is_empty() {
+            match fs::remove_file(&self.config.path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            self.bytes = 0;
+            self.pending = 0;
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        for price in rest {
+            serde_json::to_writer(&mut buf, price).expect("StockPrice serializes to JSON");
+            buf.push(b'\n');
+        }
+        let tmp = self.config.path.with_extension("tmp");
+        fs::write(&tmp, &buf).await?;
+        fs::rename(&tmp, &self.config.path).await?;
+        self.bytes = buf.len() as u64;
+        self.pending = rest.len();
+        Ok(())
+    }
+
+    fn state(&self) -> String {
+        format!(
+            "spool {} holds {} prices, {} of {} bytes used",
+            self.config.path.display(),
+            self.pending,
+            self.bytes,
+            self.config.max_bytes
+        )
+    }
+}
+
+async fn append_to(path: &Path, buf: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(buf).await?;
+    file.sync_data().await
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::models::USD;
+    use crate::store::MemoryStore;
+
+    fn config(name: &str) -> SpoolConfig {
+        let path = std::env::temp_dir().join(format!("td1-spool-{}-{name}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SpoolConfig { path, retry_secs: 0, ..SpoolConfig::default() }
+    }
+
+    fn price(symbol: &str) -> StockPrice {
+        StockPrice {
+            symbol: symbol.to_string(),
+            price: Decimal::new(100, 0),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            ..StockPrice::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn spooled_prices_survive_a_restart_and_replay_into_the_store() {
+        let config = config("replay");
+        let mut spool = Spool::open(config.clone()).await.unwrap();
+        let report = spool.append(&[price("AAPL"), price("MSFT"), price("GOOGL")]).await;
+        assert_eq!((report.spooled, report.failed), (3, 0));
+
+        let mut spool = Spool::open(config.clone()).await.unwrap();
+        assert!(!spool.is_empty());
+
+        let store = MemoryStore::new();
+        let report = spool.replay(&store, 2).await.unwrap();
+        assert_eq!(report.inserted, 3);
+        assert!(spool.is_empty());
+        assert!(!config.path.exists());
+    }
+
+    #[tokio::test]
+    async fn full_spool_drops_what_does_not_fit() {
+        let line = serde_json::to_vec(&price("AAPL")).unwrap().len() as u64 + 1;
+        let config = SpoolConfig { max_bytes: 2 * line, ..config("full") };
+        let mut spool = Spool::open(config.clone()).await.unwrap();
+
+        let report = spool.append(&[price("AAPL"), price("MSFT"), price("GOOGL")]).await;
+        assert_eq!((report.spooled, report.failed), (2, 1));
+        std::fs::remove_file(&config.path).unwrap();
+    }
+}
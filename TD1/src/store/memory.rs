@@ -0,0 +1,304 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{Backend, LatestPrice, MigrationStatus, PriceStore, SaveReport, StoreError};
+use crate::candles::{Candle, Resolution, Tick};
+use crate::models::StockPrice;
+
+/// Symbol, source and quote time: the same key the SQL backends keep unique.
+type Key = (String, String, DateTime<Utc>);
+
+/// Symbol, resolution and bucket start.
+type CandleKey = (String, Resolution, DateTime<Utc>);
+
+/// Keeps prices in a map for the life of the process. Lets the whole
+/// pipeline run without a database.
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    prices: BTreeMap<Key, Row>,
+    last_id: i64,
+    candles: BTreeMap<CandleKey, Candle>,
+}
+
+/// A price with the SQL backends' `id` and `aggregated` columns.
+struct Row {
+    id: i64,
+    price: StockPrice,
+    aggregated: bool,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn tick(row: &Row) -> Tick {
+    Tick {
+        id: row.id,
+        symbol: row.price.symbol.clone(),
+        price: row.price.price,
+        volume: row.price.volume,
+        quoted_at: row.price.quoted_at,
+    }
+}
+
+#[async_trait]
+impl PriceStore for MemoryStore {
+    fn backend(&self) -> Backend {
+        Backend::Memory
+    }
+
+    async fn migrate(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, StoreError> {
+        Ok(Vec::new())
+    }
+
+    async fn save_prices(&self, prices: &[StockPrice]) -> Result<SaveReport, StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        let mut report = SaveReport::default();
+        for p in prices {
+            let key = (p.symbol.clone(), p.source.clone(), p.quoted_at);
+            match inner.prices.entry(key) {
+                Entry::Vacant(slot) => {
+                    inner.last_id += 1;
+                    slot.insert(Row { id: inner.last_id, price: p.clone(), aggregated: false });
+                    report.inserted += 1;
+                }
+                Entry::Occupied(_) => report.skipped += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    async fn latest_prices(&self) -> Result<Vec<LatestPrice>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        let mut latest: BTreeMap<&str, &StockPrice> = BTreeMap::new();
+        for Row { price: p, .. } in inner.prices.values() {
+            let newest = latest.entry(&p.symbol).or_insert(p);
+            if p.quoted_at > newest.quoted_at {
+                *newest = p;
+            }
+        }
+        Ok(latest.into_values().map(LatestPrice::from).collect())
+    }
+
+    async fn pending_ticks(&self, limit: usize) -> Result<Vec<Tick>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        let mut ticks: Vec<Tick> = inner.prices.values().filter(|row| !row.aggregated).map(tick).collect();
+        ticks.sort_by_key(|t| t.id);
+        ticks.truncate(limit);
+        Ok(ticks)
+    }
+
+    async fn ticks_between(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        let mut ticks: Vec<Tick> = inner
+            .prices
+            .values()
+            .filter(|row| row.price.symbol == symbol && (from..to).contains(&row.price.quoted_at))
+            .map(tick)
+            .collect();
+        ticks.sort_by_key(|t| (t.quoted_at, t.id));
+        Ok(ticks)
+    }
+
+    async fn save_candles(&self, candles: &[Candle], aggregated: &[i64]) -> Result<(), StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        for c in candles {
+            inner
+                .candles
+                .insert((c.symbol.clone(), c.resolution, c.bucket_start), c.clone());
+        }
+        let ids: HashSet<i64> = aggregated.iter().copied().collect();
+        for row in inner.prices.values_mut() {
+            if ids.contains(&row.id) {
+                row.aggregated = true;
+            }
+        }
+        Ok(())
+    }
+
+    async fn find_candle(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        bucket_start: DateTime<Utc>,
+    ) -> Result<Option<Candle>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .candles
+            .get(&(symbol.to_string(), resolution, bucket_start))
+            .cloned())
+    }
+
+    async fn candles(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        limit: usize,
+    ) -> Result<Vec<Candle>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .candles
+            .values()
+            .rev()
+            .filter(|c| c.symbol == symbol && c.resolution == resolution)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn prune_prices(&self, before: DateTime<Utc>, aggregated_only: bool) -> Result<u64, StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.prices.len();
+        inner
+            .prices
+            .retain(|_, row| row.price.quoted_at >= before || (aggregated_only && !row.aggregated));
+        Ok((count - inner.prices.len()) as u64)
+    }
+
+    async fn prune_candles(&self, resolution: Resolution, before: DateTime<Utc>) -> Result<u64, StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.candles.len();
+        inner
+            .candles
+            .retain(|_, c| c.resolution != resolution || c.bucket_start >= before);
+        Ok((count - inner.candles.len()) as u64)
+    }
+
+    async fn close(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeDelta, TimeZone};
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::candles::Aggregator;
+    use crate::models::USD;
+    use crate::spool::SpoolConfig;
+    use crate::writer::{PriceWriter, WriterConfig};
+
+    fn price(symbol: &str, cents: i64, minute: u32) -> StockPrice {
+        StockPrice {
+            symbol: symbol.to_string(),
+            price: Decimal::new(cents, 2),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            quoted_at: Utc.with_ymd_and_hms(2026, 9, 1, 14, minute, 0).unwrap(),
+            fetched_at: Utc::now(),
+            ..StockPrice::default()
+        }
+    }
+
+    async fn write(store: &Arc<MemoryStore>, prices: Vec<StockPrice>) -> SaveReport {
+        let spool = SpoolConfig { enabled: false, ..SpoolConfig::default() };
+        let writer = PriceWriter::spawn(store.clone(), WriterConfig::default(), spool);
+        for p in prices {
+            writer.sender().send(p).await.unwrap();
+        }
+        writer.close().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn skips_prices_it_already_holds() {
+        let store = MemoryStore::new();
+        let report = store.save_prices(&[price("AAPL", 10_000, 0), price("AAPL", 10_100, 1)]).await.unwrap();
+        assert_eq!((report.inserted, report.skipped), (2, 0));
+
+        let report = store.save_prices(&[price("AAPL", 10_000, 0), price("MSFT", 40_000, 0)]).await.unwrap();
+        assert_eq!((report.inserted, report.skipped), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn latest_prices_holds_the_newest_quote_per_symbol() {
+        let store = MemoryStore::new();
+        let prices = [price("MSFT", 40_000, 0), price("AAPL", 10_100, 5), price("AAPL", 10_000, 0)];
+        store.save_prices(&prices).await.unwrap();
+
+        let latest = store.latest_prices().await.unwrap();
+        let latest: Vec<(&str, Decimal)> = latest.iter().map(|p| (p.symbol.as_str(), p.price)).collect();
+        assert_eq!(latest, [("AAPL", Decimal::new(10_100, 2)), ("MSFT", Decimal::new(40_000, 2))]);
+    }
+
+    #[tokio::test]
+    async fn writer_and_aggregator_roll_prices_into_candles() {
+        let store = Arc::new(MemoryStore::new());
+        let report = write(
+            &store,
+            vec![price("AAPL", 10_000, 0), price("AAPL", 10_500, 7), price("AAPL", 10_000, 0)],
+        )
+        .await;
+        assert_eq!((report.inserted, report.skipped), (2, 1));
+
+        let aggregator = Aggregator::new(store.clone(), &Resolution::ALL, None);
+        let report = aggregator.run_once().await.unwrap();
+        assert_eq!((report.ticks, report.candles), (2, 6));
+
+        let day = store.candles("AAPL", Resolution::OneDay, 10).await.unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!((day[0].open, day[0].close), (Decimal::new(10_000, 2), Decimal::new(10_500, 2)));
+        assert_eq!(day[0].tick_count, 2);
+        assert_eq!(store.candles("AAPL", Resolution::FiveMinutes, 10).await.unwrap().len(), 2);
+
+        assert_eq!(aggregator.run_once().await.unwrap().ticks, 0);
+    }
+
+    #[tokio::test]
+    async fn late_ticks_are_folded_into_candles_whose_ticks_were_pruned() {
+        let store = Arc::new(MemoryStore::new());
+        let aggregator = Aggregator::new(store.clone(), &[Resolution::OneDay], Some(TimeDelta::days(1)));
+
+        write(&store, vec![price("AAPL", 10_000, 0), price("AAPL", 10_100, 10)]).await;
+        aggregator.run_once().await.unwrap();
+        assert_eq!(store.prune_prices(Utc::now(), true).await.unwrap(), 2);
+
+        write(&store, vec![price("AAPL", 9_900, 30)]).await;
+        aggregator.run_once().await.unwrap();
+
+        let day = &store.candles("AAPL", Resolution::OneDay, 1).await.unwrap()[0];
+        assert_eq!(day.tick_count, 3);
+        assert_eq!((day.open, day.close, day.low), (Decimal::new(10_000, 2), Decimal::new(9_900, 2), Decimal::new(9_900, 2)));
+    }
+
+    #[tokio::test]
+    async fn refetched_prices_are_not_counted_twice_after_pruning() {
+        let store = Arc::new(MemoryStore::new());
+        let aggregator = Aggregator::new(store.clone(), &[Resolution::OneDay], Some(TimeDelta::days(1)));
+
+        for _ in 0..2 {
+            write(&store, vec![price("AAPL", 10_000, 0)]).await;
+            aggregator.run_once().await.unwrap();
+            assert_eq!(store.prune_prices(Utc::now(), true).await.unwrap(), 1);
+        }
+        write(&store, vec![price("AAPL", 9_900, 30)]).await;
+        aggregator.run_once().await.unwrap();
+
+        let day = &store.candles("AAPL", Resolution::OneDay, 1).await.unwrap()[0];
+        assert_eq!(day.tick_count, 2);
+        assert_eq!((day.open, day.close), (Decimal::new(10_000, 2), Decimal::new(9_900, 2)));
+    }
+}
+
@@ -0,0 +1,356 @@
+mod memory;
+mod postgres;
+mod sqlite;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::AddAssign;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::error::DatabaseError;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::PgDatabaseError;
+use sqlx::sqlite::SqliteError;
+use sqlx::{Database, Pool};
+use tracing::{error, warn};
+
+use crate::candles::{Candle, Resolution, Tick};
+use crate::config::Config;
+use crate::models::StockPrice;
+
+pub use memory::MemoryStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+// --- Errors ---
+
+#[derive(Debug)]
+pub enum StoreError {
+    Database(sqlx::Error),
+    Migrate(MigrateError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Database(e) => write!(f, "database error: {e}"),
+            StoreError::Migrate(e) => write!(f, "migration error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl StoreError {
+    /// Whether the store refused the rows themselves, e.g. for breaking a
+    /// constraint or holding a value it can't take, so sending them again
+    /// won't help. Anything else, from a lost connection to a database that
+    /// is busy, restarting or not migrated yet, may clear up.
+    pub fn is_refused(&self) -> bool {
+        match self {
+            StoreError::Database(sqlx::Error::Database(e)) => refuses_rows(e.as_ref()),
+            _ => false,
+        }
+    }
+}
+
+fn refuses_rows(e: &dyn DatabaseError) -> bool {
+    if let Some(e) = e.try_downcast_ref::<PgDatabaseError>() {
+        // SQLSTATE classes 22 (data exception) and 23 (integrity
+        // constraint violation).
+        return e.code().starts_with("22") || e.code().starts_with("23");
+    }
+    if e.try_downcast_ref::<SqliteError>().is_some() {
+        // SQLITE_TOOBIG, SQLITE_CONSTRAINT and SQLITE_MISMATCH; the low
+        // byte of an extended result code is its primary code.
+        let code = e.code().and_then(|code| code.parse::<i32>().ok());
+        return code.is_some_and(|code| matches!(code & 0xff, 18..=20));
+    }
+    false
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Database(e)
+    }
+}
+
+impl From<MigrateError> for StoreError {
+    fn from(e: MigrateError) -> Self {
+        StoreError::Migrate(e)
+    }
+}
+
+// --- Backends ---
+
+/// Where prices are kept, under `database.backend`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Postgres,
+    Sqlite,
+    /// Nothing survives a restart; meant for tests and dry runs.
+    Memory,
+}
+
+impl Backend {
+    /// Whether the backend needs `database.url`.
+    pub fn needs_url(self) -> bool {
+        self != Backend::Memory
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::Postgres => "postgres",
+            Backend::Sqlite => "sqlite",
+            Backend::Memory => "memory",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "postgres" => Ok(Backend::Postgres),
+            "sqlite" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            other => Err(format!("{other:?} (expected postgres, sqlite or memory)")),
+        }
+    }
+}
+
+/// Opens the store selected by `database.backend`.
+pub async fn connect(config: &Config) -> Result<Arc<dyn PriceStore>, Box<dyn std::error::Error>> {
+    let store: Arc<dyn PriceStore> = match config.database.backend {
+        Backend::Postgres => Arc::new(PostgresStore::connect(config).await?),
+        Backend::Sqlite => Arc::new(SqliteStore::connect(config).await?),
+        Backend::Memory => Arc::new(MemoryStore::new()),
+    };
+    Ok(store)
+}
+
+// --- Store ---
+
+/// Persistence for fetched prices. Every backend deduplicates on symbol,
+/// source and quote time.
+#[async_trait]
+pub trait PriceStore: Send + Sync {
+    fn backend(&self) -> Backend;
+
+    /// Applies pending migrations.
+    async fn migrate(&self) -> Result<(), StoreError>;
+
+    /// Every known migration and whether it has been applied.
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, StoreError>;
+
+    /// Stores `prices`, skipping ones already present.
+    async fn save_prices(&self, prices: &[StockPrice]) -> Result<SaveReport, StoreError>;
+
+    /// Newest stored price for each symbol, ordered by symbol.
+    async fn latest_prices(&self) -> Result<Vec<LatestPrice>, StoreError>;
+
+    // --- Candles ---
+
+    /// Up to `limit` ticks not yet rolled into candles, by id.
+    async fn pending_ticks(&self, limit: usize) -> Result<Vec<Tick>, StoreError>;
+
+    /// `symbol`'s ticks quoted in `[from, to)`, by quote time.
+    async fn ticks_between(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, StoreError>;
+
+    /// Inserts `candles`, replacing stored ones for the same bucket, and
+    /// marks the ticks with ids in `aggregated` as rolled up, all in one
+    /// transaction.
+    async fn save_candles(&self, candles: &[Candle], aggregated: &[i64]) -> Result<(), StoreError>;
+
+    /// The stored candle for one bucket.
+    async fn find_candle(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        bucket_start: DateTime<Utc>,
+    ) -> Result<Option<Candle>, StoreError>;
+
+    /// Up to `limit` of `symbol`'s newest candles, newest first.
+    async fn candles(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        limit: usize,
+    ) -> Result<Vec<Candle>, StoreError>;
+
+    // --- Retention ---
+
+    /// Deletes prices quoted before `before`; with `aggregated_only`, just
+    /// those already rolled into candles. Returns how many were removed.
+    async fn prune_prices(&self, before: DateTime<Utc>, aggregated_only: bool) -> Result<u64, StoreError>;
+
+    /// Deletes `resolution` candles that start before `before`. Returns how
+    /// many were removed.
+    async fn prune_candles(&self, resolution: Resolution, before: DateTime<Utc>) -> Result<u64, StoreError>;
+
+    async fn close(&self);
+}
+
+/// Counts of what happened to the prices handed to
+/// [`PriceStore::save_prices`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SaveReport {
+    pub inserted: usize,
+    pub skipped: usize,
+    /// Kept in the spool until the store is reachable again.
+    pub spooled: usize,
+    /// Refused by the store on their own, e.g. for breaking a constraint.
+    pub rejected: usize,
+    pub failed: usize,
+}
+
+impl AddAssign for SaveReport {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.skipped += other.skipped;
+        self.spooled += other.spooled;
+        self.rejected += other.rejected;
+        self.failed += other.failed;
+    }
+}
+
+impl fmt::Display for SaveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} duplicates skipped, {} spooled, {} rejected, {} failed",
+            self.inserted, self.skipped, self.spooled, self.rejected, self.failed
+        )
+    }
+}
+
+/// Saves `prices`. If the store refuses the batch, saves it again row by
+/// row so one bad row can't hold back the rest. Returns the report and the
+/// rows refused on their own; any other error is returned, since the store
+/// may take the rows later.
+pub async fn save_isolating(
+    store: &dyn PriceStore,
+    prices: &[StockPrice],
+) -> Result<(SaveReport, Vec<StockPrice>), StoreError> {
+    match store.save_prices(prices).await {
+        Ok(report) => return Ok((report, Vec::new())),
+        Err(e) if e.is_refused() => {
+            warn!("Store refused a batch of {} prices, saving them one by one: {e}", prices.len())
+        }
+        Err(e) => return Err(e),
+    }
+
+    let mut report = SaveReport::default();
+    let mut rejected = Vec::new();
+    for price in prices {
+        match store.save_prices(std::slice::from_ref(price)).await {
+            Ok(r) => report += r,
+            Err(e) if e.is_refused() => {
+                error!(
+                    "Store refused {} from {} quoted at {}: {e}",
+                    price.symbol, price.source, price.quoted_at
+                );
+                rejected.push(price.clone());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((report, rejected))
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LatestPrice {
+    pub symbol: String,
+    pub price: Decimal,
+    pub currency: String,
+    pub change_percent: Option<Decimal>,
+    pub source: String,
+    pub quoted_at: DateTime<Utc>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl From<&StockPrice> for LatestPrice {
+    fn from(p: &StockPrice) -> Self {
+        Self {
+            symbol: p.symbol.clone(),
+            price: p.price,
+            currency: p.currency.clone(),
+            change_percent: p.change_percent,
+            source: p.source.clone(),
+            quoted_at: p.quoted_at,
+            fetched_at: p.fetched_at,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Shared by the sqlx backends: lists `migrator`'s migrations against the
+/// ones recorded in `pool`.
+async fn migration_status<DB>(
+    migrator: &Migrator,
+    pool: &Pool<DB>,
+) -> Result<Vec<MigrationStatus>, StoreError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+
+    Ok(migrator
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_errors_about_the_rows_are_refusals() {
+        let passing = [
+            sqlx::Error::Io(std::io::ErrorKind::ConnectionReset.into()),
+            sqlx::Error::Tls("handshake failed".into()),
+            sqlx::Error::Protocol("unexpected message".to_string()),
+            sqlx::Error::PoolTimedOut,
+            sqlx::Error::PoolClosed,
+            sqlx::Error::WorkerCrashed,
+        ];
+        for e in passing {
+            assert!(!StoreError::from(e).is_refused());
+        }
+    }
+}
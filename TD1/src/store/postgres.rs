@@ -0,0 +1,381 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::types::Text;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+
+use super::{Backend, LatestPrice, MigrationStatus, PriceStore, SaveReport, StoreError};
+use crate::candles::{Candle, Resolution, Tick};
+use crate::config::Config;
+use crate::models::StockPrice;
+
+/// Migrations in `TD1/migrations/postgres`, embedded at compile time.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Candles per upsert, keeping 11 binds per row well under Postgres' limit.
+const CANDLES_PER_INSERT: usize = 2_000;
+
+pub struct PostgresStore {
+    pool: PgPool,
+    notify_channel: Option<String>,
+}
+
+impl PostgresStore {
+    pub async fn connect(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.database.max_connections)
+            .connect(config.database_url()?)
+            .await?;
+
+        Ok(Self {
+            pool,
+            notify_channel: config.database.notify_channel.clone(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct TickRow {
+    id: i64,
+    symbol: String,
+    price: Decimal,
+    volume: Option<i64>,
+    quoted_at: DateTime<Utc>,
+}
+
+impl From<TickRow> for Tick {
+    fn from(row: TickRow) -> Self {
+        Self {
+            id: row.id,
+            symbol: row.symbol,
+            price: row.price,
+            volume: row.volume,
+            quoted_at: row.quoted_at,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct CandleRow {
+    symbol: String,
+    resolution: Text<Resolution>,
+    bucket_start: DateTime<Utc>,
+    open: Decimal,
+    high: Decimal,
+    low: Decimal,
+    close: Decimal,
+    volume: Option<i64>,
+    tick_count: i32,
+    opened_at: DateTime<Utc>,
+    closed_at: DateTime<Utc>,
+}
+
+impl From<CandleRow> for Candle {
+    fn from(row: CandleRow) -> Self {
+        Self {
+            symbol: row.symbol,
+            resolution: row.resolution.0,
+            bucket_start: row.bucket_start,
+            open: row.open,
+            high: row.high,
+            low: row.low,
+            close: row.close,
+            volume: row.volume,
+            tick_count: row.tick_count.into(),
+            opened_at: row.opened_at,
+            closed_at: row.closed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl PriceStore for PostgresStore {
+    fn backend(&self) -> Backend {
+        Backend::Postgres
+    }
+
+    async fn migrate(&self) -> Result<(), StoreError> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, StoreError> {
+        super::migration_status(&MIGRATOR, &self.pool).await
+    }
+
+    /// Inserts `prices` in one statement, skipping rows whose symbol, source
+    /// and quote time already exist. With a notify channel, each inserted
+    /// row is also sent as a JSON `NOTIFY`, delivered when the insert
+    /// commits. The payload carries `fetched_at`, so listeners can tell a
+    /// price replayed from the spool from a fresh one.
+    async fn save_prices(&self, prices: &[StockPrice]) -> Result<SaveReport, StoreError> {
+        if prices.is_empty() {
+            return Ok(SaveReport::default());
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(if self.notify_channel.is_some() {
+            "WITH inserted AS ("
+        } else {
+            ""
+        });
+        query.push(
+            "INSERT INTO stock_prices (symbol, price, currency, source, quoted_at, fetched_at, \
+             open, high, low, volume, previous_close, change, change_percent, trading_day) ",
+        );
+        query.push_values(prices, |mut row, p| {
+            row.push_bind(&p.symbol)
+                .push_bind(p.price)
+                .push_bind(&p.currency)
+                .push_bind(&p.source)
+                .push_bind(p.quoted_at)
+                .push_bind(p.fetched_at)
+                .push_bind(p.open)
+                .push_bind(p.high)
+                .push_bind(p.low)
+                .push_bind(p.volume)
+                .push_bind(p.previous_close)
+                .push_bind(p.change)
+                .push_bind(p.change_percent)
+                .push_bind(p.trading_day);
+        });
+        query.push(" ON CONFLICT (symbol, source, quoted_at) DO NOTHING");
+
+        let inserted = match &self.notify_channel {
+            Some(channel) => {
+                query
+                    .push(
+                        " RETURNING symbol, price, source, quoted_at, fetched_at) \
+                         SELECT COUNT(*) FROM (SELECT pg_notify(",
+                    )
+                    .push_bind(channel)
+                    .push(
+                        ", json_build_object('symbol', symbol, 'price', price, \
+                         'source', source, 'timestamp', quoted_at, 'fetched_at', fetched_at)::text) \
+                         FROM inserted) AS notified",
+                    );
+                let (count,): (i64,) = query.build_query_as().fetch_one(&self.pool).await?;
+                count as usize
+            }
+            None => query.build().execute(&self.pool).await?.rows_affected() as usize,
+        };
+
+        Ok(SaveReport {
+            inserted,
+            skipped: prices.len() - inserted,
+            ..Default::default()
+        })
+    }
+
+    async fn latest_prices(&self) -> Result<Vec<LatestPrice>, StoreError> {
+        let rows = sqlx::query_as::<_, LatestPrice>(
+            r#"
+            SELECT DISTINCT ON (symbol)
+                symbol, price, currency, change_percent, source, quoted_at, fetched_at
+            FROM stock_prices
+            ORDER BY symbol, quoted_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn pending_ticks(&self, limit: usize) -> Result<Vec<Tick>, StoreError> {
+        let rows = sqlx::query_as::<_, TickRow>(
+            "SELECT id::BIGINT AS id, symbol, price, volume, quoted_at \
+             FROM stock_prices WHERE NOT aggregated ORDER BY id LIMIT $1",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Tick::from).collect())
+    }
+
+    async fn ticks_between(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, StoreError> {
+        let rows = sqlx::query_as::<_, TickRow>(
+            "SELECT id::BIGINT AS id, symbol, price, volume, quoted_at \
+             FROM stock_prices \
+             WHERE symbol = $1 AND quoted_at >= $2 AND quoted_at < $3 \
+             ORDER BY quoted_at, id",
+        )
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Tick::from).collect())
+    }
+
+    async fn save_candles(&self, candles: &[Candle], aggregated: &[i64]) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+        for chunk in candles.chunks(CANDLES_PER_INSERT) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO candles (symbol, resolution, bucket_start, open, high, low, close, \
+                 volume, tick_count, opened_at, closed_at) ",
+            );
+            query.push_values(chunk, |mut row, c| {
+                row.push_bind(&c.symbol)
+                    .push_bind(c.resolution.as_str())
+                    .push_bind(c.bucket_start)
+                    .push_bind(c.open)
+                    .push_bind(c.high)
+                    .push_bind(c.low)
+                    .push_bind(c.close)
+                    .push_bind(c.volume)
+                    .push_bind(c.tick_count as i32)
+                    .push_bind(c.opened_at)
+                    .push_bind(c.closed_at);
+            });
+            query.push(
+                " ON CONFLICT (symbol, resolution, bucket_start) DO UPDATE SET \
+                 open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, \
+                 close = EXCLUDED.close, volume = EXCLUDED.volume, \
+                 tick_count = EXCLUDED.tick_count, opened_at = EXCLUDED.opened_at, \
+                 closed_at = EXCLUDED.closed_at, updated_at = now()",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+        sqlx::query("UPDATE stock_prices SET aggregated = TRUE WHERE id = ANY($1)")
+            .bind(aggregated)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_candle(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        bucket_start: DateTime<Utc>,
+    ) -> Result<Option<Candle>, StoreError> {
+        let row = sqlx::query_as::<_, CandleRow>(
+            "SELECT symbol, resolution, bucket_start, open, high, low, close, volume, \
+             tick_count, opened_at, closed_at \
+             FROM candles WHERE symbol = $1 AND resolution = $2 AND bucket_start = $3",
+        )
+        .bind(symbol)
+        .bind(resolution.as_str())
+        .bind(bucket_start)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(Candle::from))
+    }
+
+    async fn candles(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        limit: usize,
+    ) -> Result<Vec<Candle>, StoreError> {
+        let rows = sqlx::query_as::<_, CandleRow>(
+            "SELECT symbol, resolution, bucket_start, open, high, low, close, volume, \
+             tick_count, opened_at, closed_at \
+             FROM candles WHERE symbol = $1 AND resolution = $2 \
+             ORDER BY bucket_start DESC LIMIT $3",
+        )
+        .bind(symbol)
+        .bind(resolution.as_str())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Candle::from).collect())
+    }
+
+    async fn prune_prices(&self, before: DateTime<Utc>, aggregated_only: bool) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM stock_prices WHERE quoted_at < $1 AND (aggregated OR NOT $2)")
+            .bind(before)
+            .bind(aggregated_only)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn prune_candles(&self, resolution: Resolution, before: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM candles WHERE resolution = $1 AND bucket_start < $2")
+            .bind(resolution.as_str())
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use sqlx::postgres::PgListener;
+
+    use super::*;
+    use crate::models::USD;
+
+    /// A migrated store on the database in `TD1_TEST_POSTGRES_URL`. Tests
+    /// that need one are ignored by default; run them with e.g.
+    /// `TD1_TEST_POSTGRES_URL=postgres://localhost/td1_test cargo test -- --ignored`.
+    async fn store(notify_channel: Option<&str>) -> (PostgresStore, String) {
+        let url = std::env::var("TD1_TEST_POSTGRES_URL").expect("TD1_TEST_POSTGRES_URL is set");
+        let mut config = Config::default();
+        config.database.url = Some(url.clone());
+        config.database.notify_channel = notify_channel.map(str::to_string);
+        let store = PostgresStore::connect(&config).await.unwrap();
+        store.migrate().await.unwrap();
+        (store, url)
+    }
+
+    fn price(symbol: &str) -> StockPrice {
+        StockPrice {
+            symbol: symbol.to_string(),
+            price: Decimal::new(18_750, 2),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            quoted_at: Utc::now(),
+            fetched_at: Utc::now(),
+            ..StockPrice::default()
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn inserted_prices_are_sent_as_notifications() {
+        let (store, url) = store(Some("td1_test_prices")).await;
+        let mut listener = PgListener::connect(&url).await.unwrap();
+        listener.listen("td1_test_prices").await.unwrap();
+
+        let price = price("NOTIFYTEST");
+        let report = store.save_prices(&[price.clone(), price.clone()]).await.unwrap();
+        assert_eq!(report.inserted, 1);
+
+        let payload: serde_json::Value = serde_json::from_str(listener.recv().await.unwrap().payload()).unwrap();
+        assert_eq!(payload["symbol"], "NOTIFYTEST");
+        assert_eq!(payload["price"], 187.5);
+        assert!(payload["fetched_at"].is_string());
+
+        sqlx::query("DELETE FROM stock_prices WHERE symbol = 'NOTIFYTEST'")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn bad_rows_are_refused() {
+        let (store, _) = store(None).await;
+        let e = store.save_prices(&[price("TOOLONGSYMBOL")]).await.unwrap_err();
+        assert!(e.is_refused(), "{e}");
+    }
+}
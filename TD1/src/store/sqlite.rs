@@ -0,0 +1,447 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::types::Text;
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::{Backend, LatestPrice, MigrationStatus, PriceStore, SaveReport, StoreError};
+use crate::candles::{Candle, Resolution, Tick};
+use crate::config::Config;
+use crate::models::StockPrice;
+
+/// Migrations in `TD1/migrations/sqlite`, embedded at compile time.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// Rows per INSERT, keeping 14 binds per row under SQLite's 32766 limit.
+const ROWS_PER_INSERT: usize = 2_000;
+
+/// Candles per upsert, at 12 binds per row.
+const CANDLES_PER_INSERT: usize = 2_000;
+
+/// Tick ids per `UPDATE ... WHERE id IN (...)`.
+const IDS_PER_UPDATE: usize = 10_000;
+
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Opens the database file, creating it if it doesn't exist.
+    pub async fn connect(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let options = SqliteConnectOptions::from_str(config.database_url()?)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.database.max_connections)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self { pool })
+    }
+}
+
+/// SQLite has no decimal type; prices are stored as text.
+#[derive(FromRow)]
+struct LatestRow {
+    symbol: String,
+    price: Text<Decimal>,
+    currency: String,
+    change_percent: Option<Text<Decimal>>,
+    source: String,
+    quoted_at: DateTime<Utc>,
+    fetched_at: DateTime<Utc>,
+}
+
+impl From<LatestRow> for LatestPrice {
+    fn from(row: LatestRow) -> Self {
+        Self {
+            symbol: row.symbol,
+            price: row.price.0,
+            currency: row.currency,
+            change_percent: row.change_percent.map(|p| p.0),
+            source: row.source,
+            quoted_at: row.quoted_at,
+            fetched_at: row.fetched_at,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct TickRow {
+    id: i64,
+    symbol: String,
+    price: Text<Decimal>,
+    volume: Option<i64>,
+    quoted_at: DateTime<Utc>,
+}
+
+impl From<TickRow> for Tick {
+    fn from(row: TickRow) -> Self {
+        Self {
+            id: row.id,
+            symbol: row.symbol,
+            price: row.price.0,
+            volume: row.volume,
+            quoted_at: row.quoted_at,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct CandleRow {
+    symbol: String,
+    resolution: Text<Resolution>,
+    bucket_start: DateTime<Utc>,
+    open: Text<Decimal>,
+    high: Text<Decimal>,
+    low: Text<Decimal>,
+    close: Text<Decimal>,
+    volume: Option<i64>,
+    tick_count: i64,
+    opened_at: DateTime<Utc>,
+    closed_at: DateTime<Utc>,
+}
+
+impl From<CandleRow> for Candle {
+    fn from(row: CandleRow) -> Self {
+        Self {
+            symbol: row.symbol,
+            resolution: row.resolution.0,
+            bucket_start: row.bucket_start,
+            open: row.open.0,
+            high: row.high.0,
+            low: row.low.0,
+            close: row.close.0,
+            volume: row.volume,
+            tick_count: row.tick_count,
+            opened_at: row.opened_at,
+            closed_at: row.closed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl PriceStore for SqliteStore {
+    fn backend(&self) -> Backend {
+        Backend::Sqlite
+    }
+
+    async fn migrate(&self) -> Result<(), StoreError> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, StoreError> {
+        super::migration_status(&MIGRATOR, &self.pool).await
+    }
+
+    /// Inserts `prices` in one transaction, skipping rows whose symbol,
+    /// source and quote time already exist.
+    async fn save_prices(&self, prices: &[StockPrice]) -> Result<SaveReport, StoreError> {
+        if prices.is_empty() {
+            return Ok(SaveReport::default());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+        for chunk in prices.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO stock_prices (symbol, price, currency, source, quoted_at, fetched_at, \
+                 open, high, low, volume, previous_close, change, change_percent, trading_day) ",
+            );
+            query.push_values(chunk, |mut row, p| {
+                row.push_bind(&p.symbol)
+                    .push_bind(Text(p.price))
+                    .push_bind(&p.currency)
+                    .push_bind(&p.source)
+                    .push_bind(p.quoted_at)
+                    .push_bind(p.fetched_at)
+                    .push_bind(p.open.map(Text))
+                    .push_bind(p.high.map(Text))
+                    .push_bind(p.low.map(Text))
+                    .push_bind(p.volume)
+                    .push_bind(p.previous_close.map(Text))
+                    .push_bind(p.change.map(Text))
+                    .push_bind(p.change_percent.map(Text))
+                    .push_bind(p.trading_day);
+            });
+            query.push(" ON CONFLICT (symbol, source, quoted_at) DO NOTHING");
+
+            inserted += query.build().execute(&mut *tx).await?.rows_affected() as usize;
+        }
+        tx.commit().await?;
+
+        Ok(SaveReport {
+            inserted,
+            skipped: prices.len() - inserted,
+            ..Default::default()
+        })
+    }
+
+    async fn latest_prices(&self) -> Result<Vec<LatestPrice>, StoreError> {
+        let rows = sqlx::query_as::<_, LatestRow>(
+            r#"
+            SELECT symbol, price, currency, change_percent, source, quoted_at, fetched_at
+            FROM (
+                SELECT *, ROW_NUMBER() OVER (PARTITION BY symbol ORDER BY quoted_at DESC) AS rn
+                FROM stock_prices
+            )
+            WHERE rn = 1
+            ORDER BY symbol
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(LatestPrice::from).collect())
+    }
+
+    async fn pending_ticks(&self, limit: usize) -> Result<Vec<Tick>, StoreError> {
+        let rows = sqlx::query_as::<_, TickRow>(
+            "SELECT id, symbol, price, volume, quoted_at \
+             FROM stock_prices WHERE aggregated = 0 ORDER BY id LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Tick::from).collect())
+    }
+
+    async fn ticks_between(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, StoreError> {
+        let rows = sqlx::query_as::<_, TickRow>(
+            "SELECT id, symbol, price, volume, quoted_at \
+             FROM stock_prices \
+             WHERE symbol = ? AND quoted_at >= ? AND quoted_at < ? \
+             ORDER BY quoted_at, id",
+        )
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Tick::from).collect())
+    }
+
+    async fn save_candles(&self, candles: &[Candle], aggregated: &[i64]) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+        for chunk in candles.chunks(CANDLES_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO candles (symbol, resolution, bucket_start, open, high, low, close, \
+                 volume, tick_count, opened_at, closed_at, updated_at) ",
+            );
+            let now = Utc::now();
+            query.push_values(chunk, |mut row, c| {
+                row.push_bind(&c.symbol)
+                    .push_bind(c.resolution.as_str())
+                    .push_bind(c.bucket_start)
+                    .push_bind(Text(c.open))
+                    .push_bind(Text(c.high))
+                    .push_bind(Text(c.low))
+                    .push_bind(Text(c.close))
+                    .push_bind(c.volume)
+                    .push_bind(c.tick_count)
+                    .push_bind(c.opened_at)
+                    .push_bind(c.closed_at)
+                    .push_bind(now);
+            });
+            query.push(
+                " ON CONFLICT (symbol, resolution, bucket_start) DO UPDATE SET \
+                 open = excluded.open, high = excluded.high, low = excluded.low, \
+                 close = excluded.close, volume = excluded.volume, \
+                 tick_count = excluded.tick_count, opened_at = excluded.opened_at, \
+                 closed_at = excluded.closed_at, updated_at = excluded.updated_at",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+        for chunk in aggregated.chunks(IDS_PER_UPDATE) {
+            let mut query = QueryBuilder::<Sqlite>::new("UPDATE stock_prices SET aggregated = 1 WHERE id IN (");
+            let mut ids = query.separated(", ");
+            for id in chunk {
+                ids.push_bind(id);
+            }
+            query.push(")");
+            query.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_candle(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        bucket_start: DateTime<Utc>,
+    ) -> Result<Option<Candle>, StoreError> {
+        let row = sqlx::query_as::<_, CandleRow>(
+            "SELECT symbol, resolution, bucket_start, open, high, low, close, volume, \
+             tick_count, opened_at, closed_at \
+             FROM candles WHERE symbol = ? AND resolution = ? AND bucket_start = ?",
+        )
+        .bind(symbol)
+        .bind(resolution.as_str())
+        .bind(bucket_start)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(Candle::from))
+    }
+
+    async fn candles(
+        &self,
+        symbol: &str,
+        resolution: Resolution,
+        limit: usize,
+    ) -> Result<Vec<Candle>, StoreError> {
+        let rows = sqlx::query_as::<_, CandleRow>(
+            "SELECT symbol, resolution, bucket_start, open, high, low, close, volume, \
+             tick_count, opened_at, closed_at \
+             FROM candles WHERE symbol = ? AND resolution = ? \
+             ORDER BY bucket_start DESC LIMIT ?",
+        )
+        .bind(symbol)
+        .bind(resolution.as_str())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Candle::from).collect())
+    }
+
+    async fn prune_prices(&self, before: DateTime<Utc>, aggregated_only: bool) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM stock_prices WHERE quoted_at < ? AND (aggregated = 1 OR NOT ?)")
+            .bind(before)
+            .bind(aggregated_only)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn prune_candles(&self, resolution: Resolution, before: DateTime<Utc>) -> Result<u64, StoreError> {
+        let result = sqlx::query("DELETE FROM candles WHERE resolution = ? AND bucket_start < ?")
+            .bind(resolution.as_str())
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::models::USD;
+    use crate::spool::{Spool, SpoolConfig};
+
+    /// A migrated in-memory database. It lives as long as the pool's one
+    /// connection.
+    async fn store() -> SqliteStore {
+        let mut config = Config::default();
+        config.database.url = Some("sqlite::memory:".to_string());
+        config.database.max_connections = 1;
+        let store = SqliteStore::connect(&config).await.unwrap();
+        store.migrate().await.unwrap();
+        store
+    }
+
+    fn price(symbol: &str, price: &str, minute: u32) -> StockPrice {
+        StockPrice {
+            symbol: symbol.to_string(),
+            price: price.parse().unwrap(),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            quoted_at: Utc.with_ymd_and_hms(2026, 9, 1, 14, minute, 0).unwrap(),
+            fetched_at: Utc.with_ymd_and_hms(2026, 9, 1, 14, minute, 30).unwrap(),
+            change_percent: Some("-0.25".parse().unwrap()),
+            ..StockPrice::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_applies_every_migration() {
+        let store = store().await;
+        let status = store.migration_status().await.unwrap();
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|m| m.applied));
+    }
+
+    #[tokio::test]
+    async fn saves_are_idempotent_and_keep_exact_prices() {
+        let store = store().await;
+        let prices = [price("AAPL", "187.123456", 0), price("AAPL", "187.5", 1), price("MSFT", "402.01", 0)];
+        let report = store.save_prices(&prices).await.unwrap();
+        assert_eq!((report.inserted, report.skipped), (3, 0));
+        let report = store.save_prices(&prices[..2]).await.unwrap();
+        assert_eq!((report.inserted, report.skipped), (0, 2));
+
+        let latest = store.latest_prices().await.unwrap();
+        assert_eq!(latest.len(), 2);
+        assert_eq!((latest[0].symbol.as_str(), latest[0].price.to_string()), ("AAPL", "187.5".to_string()));
+        assert_eq!(latest[0].quoted_at, prices[1].quoted_at);
+        assert_eq!(latest[0].fetched_at, prices[1].fetched_at);
+        assert_eq!(latest[1].change_percent, prices[2].change_percent);
+    }
+
+    #[tokio::test]
+    async fn replay_quarantines_rows_the_store_refuses() {
+        let store = store().await;
+        sqlx::query(
+            "CREATE TRIGGER refuse_bad BEFORE INSERT ON stock_prices WHEN NEW.symbol = 'BAD' \
+             BEGIN SELECT RAISE(ABORT, 'refused'); END",
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("td1-spool-{}-quarantine.jsonl", std::process::id()));
+        let config = SpoolConfig { path, retry_secs: 0, ..SpoolConfig::default() };
+        let rejects = config.path.with_extension("rejected.jsonl");
+        let _ = std::fs::remove_file(&rejects);
+
+        let mut spool = Spool::open(config).await.unwrap();
+        spool.append(&[price("AAPL", "187.5", 0), price("BAD", "1", 0), price("MSFT", "402.01", 0)]).await;
+        let report = spool.replay(&store, 10).await.unwrap();
+
+        assert_eq!((report.inserted, report.rejected), (2, 1));
+        assert!(spool.is_empty());
+        let rejected = std::fs::read_to_string(&rejects).unwrap();
+        assert_eq!(rejected.lines().count(), 1);
+        assert!(rejected.contains("\"BAD\""));
+        std::fs::remove_file(&rejects).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_keeps_prices_the_store_cannot_take_yet() {
+        let mut config = Config::default();
+        config.database.url = Some("sqlite::memory:".to_string());
+        config.database.max_connections = 1;
+        let store = SqliteStore::connect(&config).await.unwrap();
+
+        let path = std::env::temp_dir().join(format!("td1-spool-{}-not-migrated.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut spool = Spool::open(SpoolConfig { path, retry_secs: 0, ..SpoolConfig::default() }).await.unwrap();
+        spool.append(&[price("AAPL", "187.5", 0), price("MSFT", "402.01", 0)]).await;
+
+        let report = spool.replay(&store, 10).await.unwrap();
+        assert_eq!((report.inserted, report.rejected), (0, 0));
+        assert!(!spool.is_empty());
+
+        store.migrate().await.unwrap();
+        let report = spool.replay(&store, 10).await.unwrap();
+        assert_eq!((report.inserted, report.rejected), (2, 0));
+        assert!(spool.is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::store::{self, PriceStore, SaveReport};
+use crate::models::StockPrice;
+use crate::spool::{Spool, SpoolConfig};
+
+/// Rows per INSERT are capped so the statement stays under Postgres'
+/// 65535 bind parameter limit.
+pub const MAX_BATCH_SIZE: usize = 4_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WriterConfig {
+    /// Flush once this many prices are buffered.
+    pub batch_size: usize,
+    /// Flush whatever is buffered at least this often.
+    pub flush_interval_ms: u64,
+    /// Prices that can be queued before fetchers have to wait.
+    pub channel_capacity: usize,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            flush_interval_ms: 1_000,
+            channel_capacity: 1_000,
+        }
+    }
+}
+
+/// Handle to the batching writer task. Prices sent through
+/// [`sender`](Self::sender) are written in multi-row INSERTs; the channel is
+/// bounded, so a slow database makes senders wait.
+pub struct PriceWriter {
+    tx: mpsc::Sender<StockPrice>,
+    handle: JoinHandle<SaveReport>,
+}
+
+impl PriceWriter {
+    pub fn spawn(store: Arc<dyn PriceStore>, config: WriterConfig, spool: SpoolConfig) -> Self {
+        let (tx, rx) = mpsc::channel(config.channel_capacity);
+        let handle = tokio::spawn(run(store, config, spool, rx));
+        Self { tx, handle }
+    }
+
+    pub fn sender(&self) -> &mpsc::Sender<StockPrice> {
+        &self.tx
+    }
+
+    /// Closes the channel, waits for the remaining prices to be flushed and
+    /// returns the totals.
+    pub async fn close(self) -> Result<SaveReport, JoinError> {
+        drop(self.tx);
+        self.handle.await
+    }
+}
+
+async fn run(
+    store: Arc<dyn PriceStore>,
+    config: WriterConfig,
+    spool_config: SpoolConfig,
+    mut rx: mpsc::Receiver<StockPrice>,
+) -> SaveReport {
+    let mut spool = if spool_config.enabled {
+        match Spool::open(spool_config).await {
+            Ok(spool) => Some(spool),
+            Err(e) => {
+                error!("Cannot open spool, failed writes will be dropped: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut total = SaveReport::default();
+    let mut batch = Vec::with_capacity(config.batch_size);
+
+    let mut ticker = interval(Duration::from_millis(config.flush_interval_ms));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(price) => {
+                    batch.push(price);
+                    if batch.len() >= config.batch_size {
+                        total += flush(store.as_ref(), &mut spool, &mut batch, config.batch_size).await;
+                    }
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if !batch.is_empty() {
+                    total += flush(store.as_ref(), &mut spool, &mut batch, config.batch_size).await;
+                } else if let Some(spool) = &mut spool {
+                    total += drain(store.as_ref(), spool, config.batch_size).await;
+                }
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        total += flush(store.as_ref(), &mut spool, &mut batch, config.batch_size).await;
+    }
+    info!("Writer stopped ({total})");
+    total
+}
+
+/// Saves `batch`. With a spool, failed writes are spooled instead of
+/// dropped, and while the spool holds anything new prices queue up behind
+/// it so the store sees them in order. Rows the store refuses on their own
+/// are quarantined and never queued.
+async fn flush(
+    store: &dyn PriceStore,
+    spool: &mut Option<Spool>,
+    batch: &mut Vec<StockPrice>,
+    batch_size: usize,
+) -> SaveReport {
+    let mut report = SaveReport::default();
+    let spool = match spool {
+        Some(spool) => {
+            report += drain(store, spool, batch_size).await;
+            if !spool.is_empty() {
+                report += spool.append(batch).await;
+                batch.clear();
+                return report;
+            }
+            Some(spool)
+        }
+        None => None,
+    };
+
+    match store::save_isolating(store, batch).await {
+        Ok((r, rejected)) => {
+            debug!("Flushed {} prices ({r})", batch.len());
+            report += r;
+            report += match spool {
+                Some(spool) => spool.quarantine(&rejected).await,
+                None => SaveReport { rejected: rejected.len(), ..Default::default() },
+            };
+        }
+        Err(e) => {
+            error!("Store unavailable while flushing {} prices: {e}", batch.len());
+            report += match spool {
+                Some(spool) => spool.append(batch).await,
+                None => SaveReport { failed: batch.len(), ..Default::default() },
+            };
+        }
+    }
+    batch.clear();
+    report
+}
+
+/// Replays the spool if it holds anything and a retry is due.
+async fn drain(store: &dyn PriceStore, spool: &mut Spool, batch_size: usize) -> SaveReport {
+    if spool.is_empty() || !spool.retry_due() {
+        return SaveReport::default();
+    }
+    match spool.replay(store, batch_size).await {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Cannot replay spool: {e}");
+            SaveReport::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use tokio::sync::mpsc::error::TrySendError;
+
+    use super::*;
+    use crate::models::USD;
+    use crate::config::Config;
+    use crate::spool::SpoolConfig;
+    use crate::store::{MemoryStore, SqliteStore};
+
+    fn price(symbol: &str) -> StockPrice {
+        StockPrice {
+            symbol: symbol.to_string(),
+            price: Decimal::new(100, 0),
+            currency: USD.to_string(),
+            source: "mock".to_string(),
+            ..StockPrice::default()
+        }
+    }
+
+    fn no_spool() -> SpoolConfig {
+        SpoolConfig { enabled: false, ..SpoolConfig::default() }
+    }
+
+    #[tokio::test]
+    async fn full_channel_pushes_back_on_senders() {
+        let config = WriterConfig { channel_capacity: 2, ..WriterConfig::default() };
+        let writer = PriceWriter::spawn(Arc::new(MemoryStore::new()), config, no_spool());
+
+        // The writer task can't run before this test yields, so nothing
+        // has been drained yet.
+        writer.sender().try_send(price("AAPL")).unwrap();
+        writer.sender().try_send(price("MSFT")).unwrap();
+        assert!(matches!(writer.sender().try_send(price("GOOGL")), Err(TrySendError::Full(_))));
+
+        let report = writer.close().await.unwrap();
+        assert_eq!((report.inserted, report.failed), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn batches_are_flushed_when_full_and_on_close() {
+        let store = Arc::new(MemoryStore::new());
+        let config = WriterConfig { batch_size: 2, ..WriterConfig::default() };
+        let writer = PriceWriter::spawn(store.clone(), config, no_spool());
+
+        for symbol in ["AAPL", "MSFT", "GOOGL"] {
+            writer.sender().send(price(symbol)).await.unwrap();
+        }
+        writer.sender().send(price("AAPL")).await.unwrap();
+        let report = writer.close().await.unwrap();
+
+        assert_eq!((report.inserted, report.skipped), (3, 1));
+        assert_eq!(store.latest_prices().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn writes_that_fail_for_now_are_spooled_not_rejected() {
+        // Without migrations every insert fails with "no such table", which
+        // a later try can get past.
+        let mut config = Config::default();
+        config.database.url = Some("sqlite::memory:".to_string());
+        config.database.max_connections = 1;
+        let store = SqliteStore::connect(&config).await.unwrap();
+
+        let path = std::env::temp_dir().join(format!("td1-spool-{}-writer.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let spool = SpoolConfig { path: path.clone(), ..SpoolConfig::default() };
+        let writer = PriceWriter::spawn(Arc::new(store), WriterConfig::default(), spool);
+        writer.sender().send(price("AAPL")).await.unwrap();
+        writer.sender().send(price("MSFT")).await.unwrap();
+        let report = writer.close().await.unwrap();
+
+        assert_eq!((report.spooled, report.rejected, report.failed), (2, 0, 0));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert!(!path.with_extension("rejected.jsonl").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use sqlx::postgres::PgListener;
+
+use crate::hub::Hub;
+use crate::protocol::{normalize_symbol, PriceUpdate};
+
+/// Wait before reconnecting after the database connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub struct BridgeConfig {
+    /// Postgres database TD1 writes to. The bridge is off when unset.
+    pub database_url: Option<String>,
+    /// Channel TD1 sends `NOTIFY`s on (its `database.notify_channel`).
+    pub channel: String,
+    /// Prices TD1 fetched longer ago than this, such as ones it replays
+    /// after a database outage, are not relayed. `None` relays everything.
+    pub max_age: Option<Duration>,
+}
+
+/// A price as TD1 sends it.
+#[derive(Deserialize)]
+struct Notification {
+    #[serde(flatten)]
+    update: PriceUpdate,
+    /// Missing from older TD1 versions.
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+/// Relays the prices TD1 stores to subscribed clients. TD1 sends each
+/// inserted price as a JSON `NOTIFY`; prices stored while the bridge is
+/// disconnected are not replayed.
+pub async fn run(hub: Hub, database_url: String, channel: String, max_age: Option<Duration>) {
+    loop {
+        if let Err(e) = listen(&hub, &database_url, &channel, max_age).await {
+            error!("Price bridge failed: {}; reconnecting in {:?}", e, RECONNECT_DELAY);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn listen(
+    hub: &Hub,
+    database_url: &str,
+    channel: &str,
+    max_age: Option<Duration>,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect(database_url).await?;
+    listener.listen(channel).await?;
+    info!("Relaying prices from Postgres channel {:?}", channel);
+
+    loop {
+        let notification = listener.recv().await?;
+        match parse(notification.payload()) {
+            Ok(Notification { update, fetched_at }) => {
+                let age = fetched_at.and_then(|at| (Utc::now() - at).to_std().ok());
+                match age.filter(|age| max_age.is_some_and(|max| *age > max)) {
+                    Some(age) => debug!("Not relaying {} fetched {:?} ago", update.symbol, age),
+                    None => {
+                        hub.publish(update);
+                    }
+                }
+            }
+            Err(e) => warn!("Ignoring notification on {:?}: {}", channel, e),
+        }
+    }
+}
+
+fn parse(payload: &str) -> Result<Notification, String> {
+    let mut notification: Notification = serde_json::from_str(payload).map_err(|e| e.to_string())?;
+    notification.update.symbol = normalize_symbol(&notification.update.symbol, false)?;
+    Ok(notification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_td1_payloads_with_and_without_fetched_at() {
+        let payload = r#"{"symbol": "aapl", "price": 187.5, "source": "mock", "timestamp": "2026-09-01T21:00:00+00:00", "fetched_at": "2026-09-02T08:00:00+00:00"}"#;
+        let notification = parse(payload).unwrap();
+        assert_eq!(notification.update.symbol, "AAPL");
+        assert_eq!(notification.fetched_at, Some("2026-09-02T08:00:00Z".parse().unwrap()));
+
+        let payload = r#"{"symbol": "MSFT", "price": 1.0, "source": "mock", "timestamp": "2026-09-01T21:00:00Z"}"#;
+        assert_eq!(parse(payload).unwrap().fetched_at, None);
+    }
+}
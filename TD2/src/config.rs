@@ -0,0 +1,88 @@
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::bridge::BridgeConfig;
+use crate::hub::LagPolicy;
+use crate::protocol::normalize_symbol;
+use crate::simulator::SimulatorConfig;
+
+pub struct Config {
+    pub addr: String,
+    /// Updates queued per client before `on_lag` applies.
+    pub client_queue: usize,
+    pub on_lag: LagPolicy,
+    pub simulator: SimulatorConfig,
+    pub bridge: BridgeConfig,
+}
+
+impl Config {
+    /// Reads `TD2_*` environment variables, falling back to defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let config = Self {
+            addr: env::var("TD2_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string()),
+            client_queue: env_or("TD2_CLIENT_QUEUE", 256)?,
+            on_lag: env_or("TD2_ON_LAG", LagPolicy::Resync)?,
+            simulator: SimulatorConfig {
+                enabled: env_or("TD2_SIMULATOR", false)?,
+                symbols: symbols_env("TD2_SIM_SYMBOLS", "AAPL,MSFT,GOOGL,AMZN,BTC-USD")?,
+                interval: Duration::from_millis(env_or("TD2_SIM_INTERVAL_MS", 1_000)?),
+                drift: env_or("TD2_SIM_DRIFT", 0.05)?,
+                volatility: env_or("TD2_SIM_VOLATILITY", 0.3)?,
+            },
+            bridge: BridgeConfig {
+                database_url: env::var("TD2_DATABASE_URL").ok().filter(|url| !url.is_empty()),
+                channel: env::var("TD2_NOTIFY_CHANNEL").unwrap_or_else(|_| "stock_prices".to_string()),
+                max_age: match env_or("TD2_BRIDGE_MAX_AGE_SECS", 60)? {
+                    0 => None,
+                    secs => Some(Duration::from_secs(secs)),
+                },
+            },
+        };
+
+        if config.client_queue == 0 {
+            return Err("TD2_CLIENT_QUEUE must be greater than 0".to_string());
+        }
+        let sim = &config.simulator;
+        if sim.enabled {
+            if sim.symbols.is_empty() {
+                return Err("TD2_SIM_SYMBOLS must list at least one symbol".to_string());
+            }
+            if sim.interval.is_zero() {
+                return Err("TD2_SIM_INTERVAL_MS must be greater than 0".to_string());
+            }
+            if !sim.drift.is_finite() || !sim.volatility.is_finite() || sim.volatility < 0.0 {
+                return Err(
+                    "TD2_SIM_DRIFT and TD2_SIM_VOLATILITY must be numbers, volatility at least 0"
+                        .to_string(),
+                );
+            }
+        }
+        if config.bridge.channel.is_empty() {
+            return Err("TD2_NOTIFY_CHANNEL must not be empty".to_string());
+        }
+        Ok(config)
+    }
+}
+
+/// A comma-separated list of symbols, uppercased.
+fn symbols_env(key: &str, default: &str) -> Result<Vec<String>, String> {
+    let list = env::var(key).unwrap_or_else(|_| default.to_string());
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| normalize_symbol(s, false).map_err(|e| format!("{}: {}", key, e)))
+        .collect()
+}
+
+fn env_or<T>(key: &str, default: T) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(key) {
+        Ok(v) => v.parse().map_err(|e| format!("{}: {}", key, e)),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::protocol::PriceUpdate;
+
+/// What to do with a client whose queue is full when an update arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Skip updates until the queue drains, then tell the client how many
+    /// it missed and send the newest skipped price of each symbol.
+    Resync,
+    /// Close the connection.
+    Drop,
+}
+
+impl FromStr for LagPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "resync" => Ok(LagPolicy::Resync),
+            "drop" => Ok(LagPolicy::Drop),
+            other => Err(format!("{:?} (expected resync or drop)", other)),
+        }
+    }
+}
+
+/// Whether subscription `topic` covers `symbol`. `*` in a topic matches any
+/// run of characters, so `*` matches everything and `BTC-*` every symbol
+/// starting with `BTC-`.
+fn matches(topic: &str, symbol: &str) -> bool {
+    let mut parts = topic.split('*');
+    let Some(mut rest) = symbol.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// --- Registry ---
+
+struct Client {
+    tx: mpsc::Sender<PriceUpdate>,
+    topics: BTreeSet<String>,
+    /// Updates skipped because the queue was full.
+    missed: u64,
+    /// The newest skipped update of each symbol that hasn't been queued
+    /// since. Sent once the queue drains, so the client ends up with the
+    /// latest prices rather than the ones that happened to fit.
+    skipped: BTreeMap<String, PriceUpdate>,
+}
+
+/// Who is subscribed to what. Exact symbols are indexed so a publish only
+/// scans the clients that hold wildcard topics.
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    clients: HashMap<u64, Client>,
+    by_symbol: HashMap<String, HashSet<u64>>,
+    wildcards: HashSet<u64>,
+}
+
+impl Registry {
+    fn subscribe(&mut self, id: u64, topics: Vec<String>) -> Vec<String> {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return Vec::new();
+        };
+        for topic in topics {
+            if topic.contains('*') {
+                self.wildcards.insert(id);
+            } else {
+                self.by_symbol.entry(topic.clone()).or_default().insert(id);
+            }
+            client.topics.insert(topic);
+        }
+        client.topics.iter().cloned().collect()
+    }
+
+    fn unsubscribe(&mut self, id: u64, topics: &[String]) -> Vec<String> {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return Vec::new();
+        };
+        for topic in topics {
+            if client.topics.remove(topic) && !topic.contains('*') {
+                unindex(&mut self.by_symbol, topic, id);
+            }
+        }
+        if !client.topics.iter().any(|t| t.contains('*')) {
+            self.wildcards.remove(&id);
+        }
+        // A resync only covers what the client still subscribes to.
+        let topics = &client.topics;
+        client.skipped.retain(|symbol, _| topics.iter().any(|t| matches(t, symbol)));
+        client.topics.iter().cloned().collect()
+    }
+
+    fn remove(&mut self, id: u64) {
+        let Some(client) = self.clients.remove(&id) else {
+            return;
+        };
+        for topic in &client.topics {
+            unindex(&mut self.by_symbol, topic, id);
+        }
+        self.wildcards.remove(&id);
+    }
+
+    fn recipients(&self, symbol: &str) -> HashSet<u64> {
+        let mut ids = self.by_symbol.get(symbol).cloned().unwrap_or_default();
+        for &id in &self.wildcards {
+            if self.clients[&id].topics.iter().any(|t| matches(t, symbol)) {
+                ids.insert(id);
+            }
+        }
+        ids
+    }
+}
+
+fn unindex(by_symbol: &mut HashMap<String, HashSet<u64>>, symbol: &str, id: u64) {
+    if let Some(ids) = by_symbol.get_mut(symbol) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            by_symbol.remove(symbol);
+        }
+    }
+}
+
+// --- Hub ---
+
+/// Routes each published price update to the clients subscribed to its
+/// symbol. Every client has its own bounded queue, so a slow one only
+/// delays itself.
+#[derive(Clone)]
+pub struct Hub {
+    registry: Arc<Mutex<Registry>>,
+    capacity: usize,
+    on_lag: LagPolicy,
+}
+
+impl Hub {
+    pub fn new(capacity: usize, on_lag: LagPolicy) -> Self {
+        Self {
+            registry: Arc::default(),
+            capacity,
+            on_lag,
+        }
+    }
+
+    /// Registers a client with no subscriptions. It is unregistered when
+    /// the returned `Subscriber` is dropped.
+    pub fn connect(&self) -> Subscriber {
+        let (tx, rx) = mpsc::channel(self.capacity);
+
+        let mut registry = self.registry.lock().unwrap();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.clients.insert(
+            id,
+            Client {
+                tx,
+                topics: BTreeSet::new(),
+                missed: 0,
+                skipped: BTreeMap::new(),
+            },
+        );
+
+        Subscriber {
+            id,
+            hub: self.clone(),
+            rx,
+        }
+    }
+
+    /// Queues `update` for every subscribed client. Returns how many
+    /// received it.
+    pub fn publish(&self, update: PriceUpdate) -> usize {
+        let mut registry = self.registry.lock().unwrap();
+        let mut delivered = 0;
+        let mut dropped = Vec::new();
+
+        for id in registry.recipients(&update.symbol) {
+            let Some(client) = registry.clients.get_mut(&id) else {
+                continue;
+            };
+            match client.tx.try_send(update.clone()) {
+                Ok(()) => {
+                    client.skipped.remove(&update.symbol);
+                    delivered += 1;
+                }
+                Err(TrySendError::Full(update)) if self.on_lag == LagPolicy::Resync => {
+                    client.missed += 1;
+                    client.skipped.insert(update.symbol.clone(), update);
+                }
+                Err(_) => dropped.push(id),
+            }
+        }
+
+        // Dropping a client's sender ends its stream once the queue drains.
+        for id in dropped {
+            registry.remove(id);
+        }
+        delivered
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.registry.lock().unwrap().clients.len()
+    }
+}
+
+/// One connection's side of the hub.
+pub struct Subscriber {
+    id: u64,
+    hub: Hub,
+    rx: mpsc::Receiver<PriceUpdate>,
+}
+
+/// What a lagging client missed.
+pub struct Missed {
+    /// Updates skipped since the last report.
+    pub count: u64,
+    /// The newest skipped update of each symbol, newer than anything the
+    /// client has received for it.
+    pub latest: Vec<PriceUpdate>,
+}
+
+impl Subscriber {
+    /// The next update for this client, or `None` once the hub has dropped
+    /// it for lagging.
+    pub async fn recv(&mut self) -> Option<PriceUpdate> {
+        self.rx.recv().await
+    }
+
+    /// What the client missed since the last report, once everything
+    /// queued before has been received. Waiting for the queue to drain
+    /// keeps older queued updates from arriving after the latest ones.
+    pub fn take_missed(&self) -> Option<Missed> {
+        let mut registry = self.hub.registry.lock().unwrap();
+        let client = registry.clients.get_mut(&self.id)?;
+        if client.missed == 0 || !self.rx.is_empty() {
+            return None;
+        }
+        Some(Missed {
+            count: std::mem::take(&mut client.missed),
+            latest: std::mem::take(&mut client.skipped).into_values().collect(),
+        })
+    }
+
+    /// Adds `topics` and returns all of the client's topics.
+    pub fn subscribe(&self, topics: Vec<String>) -> Vec<String> {
+        self.hub.registry.lock().unwrap().subscribe(self.id, topics)
+    }
+
+    /// Removes `topics` and returns the client's remaining topics.
+    pub fn unsubscribe(&self, topics: &[String]) -> Vec<String> {
+        self.hub.registry.lock().unwrap().unsubscribe(self.id, topics)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.hub.registry.lock().unwrap().remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn update(symbol: &str) -> PriceUpdate {
+        PriceUpdate {
+            symbol: symbol.to_string(),
+            price: 1.0,
+            source: "test".to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parses_lag_policies() {
+        assert_eq!("resync".parse(), Ok(LagPolicy::Resync));
+        assert_eq!("drop".parse(), Ok(LagPolicy::Drop));
+        assert!("skip".parse::<LagPolicy>().is_err());
+    }
+
+    #[test]
+    fn matches_exact_and_wildcard_topics() {
+        assert!(matches("AAPL", "AAPL"));
+        assert!(!matches("AAPL", "AAPLX"));
+        assert!(matches("*", "AAPL"));
+        assert!(matches("BTC-*", "BTC-USD"));
+        assert!(!matches("BTC-*", "ETH-USD"));
+        assert!(matches("*-USD", "BTC-USD"));
+        assert!(matches("B*-*D", "BTC-USD"));
+        assert!(!matches("B*-*D", "BTC-EUR"));
+        assert!(!matches("A*A", "A"));
+    }
+
+    #[test]
+    fn publish_reaches_only_subscribed_clients() {
+        let hub = Hub::new(8, LagPolicy::Resync);
+        let mut exact = hub.connect();
+        let mut wildcard = hub.connect();
+        exact.subscribe(vec!["AAPL".to_string()]);
+        wildcard.subscribe(vec!["BTC-*".to_string()]);
+
+        assert_eq!(hub.publish(update("AAPL")), 1);
+        assert_eq!(hub.publish(update("BTC-USD")), 1);
+        assert_eq!(hub.publish(update("MSFT")), 0);
+        assert_eq!(exact.rx.try_recv().unwrap().symbol, "AAPL");
+        assert_eq!(wildcard.rx.try_recv().unwrap().symbol, "BTC-USD");
+
+        exact.unsubscribe(&["AAPL".to_string()]);
+        assert_eq!(hub.publish(update("AAPL")), 0);
+    }
+
+    #[test]
+    fn lagging_clients_get_the_newest_skipped_price_of_each_symbol() {
+        let hub = Hub::new(1, LagPolicy::Resync);
+        let mut client = hub.connect();
+        client.subscribe(vec!["AAPL".to_string(), "MSFT".to_string()]);
+        for (symbol, price) in [("AAPL", 1.0), ("AAPL", 2.0), ("MSFT", 3.0), ("AAPL", 4.0)] {
+            hub.publish(PriceUpdate { price, ..update(symbol) });
+        }
+
+        // Reported only once the queued update is out of the way.
+        assert!(client.take_missed().is_none());
+        assert_eq!(client.rx.try_recv().unwrap().price, 1.0);
+        let missed = client.take_missed().unwrap();
+        assert_eq!(missed.count, 3);
+        let latest: Vec<(&str, f64)> = missed.latest.iter().map(|u| (u.symbol.as_str(), u.price)).collect();
+        assert_eq!(latest, [("AAPL", 4.0), ("MSFT", 3.0)]);
+    }
+
+    #[test]
+    fn a_queued_update_supersedes_a_skipped_one() {
+        let hub = Hub::new(1, LagPolicy::Resync);
+        let mut client = hub.connect();
+        client.subscribe(vec!["AAPL".to_string()]);
+        hub.publish(PriceUpdate { price: 1.0, ..update("AAPL") });
+        hub.publish(PriceUpdate { price: 2.0, ..update("AAPL") });
+        client.rx.try_recv().unwrap();
+        hub.publish(PriceUpdate { price: 3.0, ..update("AAPL") });
+
+        assert_eq!(client.rx.try_recv().unwrap().price, 3.0);
+        let missed = client.take_missed().unwrap();
+        assert_eq!(missed.count, 1);
+        assert!(missed.latest.is_empty());
+    }
+
+    #[test]
+    fn full_queues_drop_the_client_under_the_drop_policy() {
+        let hub = Hub::new(1, LagPolicy::Drop);
+        let client = hub.connect();
+        client.subscribe(vec!["AAPL".to_string()]);
+        hub.publish(update("AAPL"));
+        hub.publish(update("AAPL"));
+        assert_eq!(hub.client_count(), 0);
+    }
+
+    #[test]
+    fn resync_skips_symbols_the_client_unsubscribed_from() {
+        let hub = Hub::new(1, LagPolicy::Resync);
+        let mut client = hub.connect();
+        client.subscribe(vec!["AAPL".to_string(), "BTC-*".to_string()]);
+        for symbol in ["AAPL", "AAPL", "MSFT", "BTC-USD"] {
+            hub.publish(update(symbol));
+        }
+        client.unsubscribe(&["BTC-*".to_string()]);
+
+        client.rx.try_recv().unwrap();
+        let missed = client.take_missed().unwrap();
+        assert_eq!(missed.count, 2);
+        let symbols: Vec<&str> = missed.latest.iter().map(|u| u.symbol.as_str()).collect();
+        assert_eq!(symbols, ["AAPL"]);
+    }
+}
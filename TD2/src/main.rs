@@ -1,10 +1,54 @@
+mod bridge;
+mod config;
+mod hub;
+mod protocol;
+mod simulator;
+
+use dotenvy::dotenv;
 use env_logger::{Builder, Target};
+use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
-use log::{error, info, LevelFilter};
+use log::{error, info, warn, LevelFilter};
 use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{accept_async, WebSocketStream};
+
+use config::Config;
+use hub::{Hub, Subscriber};
+use protocol::{ClientMessage, ErrorCode, ServerMessage};
+use simulator::Simulator;
+
+type WsSink = SplitSink<WebSocketStream<TcpStream>, Message>;
+
+async fn send(write: &mut WsSink, message: &ServerMessage) -> Result<(), tungstenite::Error> {
+    write.send(Message::Text(message.to_json())).await
+}
 
-async fn handle_client(stream: TcpStream) {
+/// Applies one client request and returns the reply.
+fn handle_message(
+    id: Option<u64>,
+    message: ClientMessage,
+    subscriber: &Subscriber,
+    hub: &Hub,
+) -> ServerMessage {
+    match message {
+        ClientMessage::Subscribe { symbols } => ServerMessage::Ack {
+            id,
+            symbols: Some(subscriber.subscribe(symbols)),
+        },
+        ClientMessage::Unsubscribe { symbols } => ServerMessage::Ack {
+            id,
+            symbols: Some(subscriber.unsubscribe(&symbols)),
+        },
+        ClientMessage::PriceUpdate(update) => {
+            hub.publish(update);
+            ServerMessage::Ack { id, symbols: None }
+        }
+        ClientMessage::Ping => ServerMessage::Pong { id },
+    }
+}
+
+async fn handle_client(stream: TcpStream, hub: Hub) {
     let addr = stream.peer_addr().unwrap();
     info!("New connection from {}", addr);
 
@@ -17,38 +61,100 @@ async fn handle_client(stream: TcpStream) {
     };
 
     let (mut write, mut read) = ws_stream.split();
+    let mut subscriber = hub.connect();
 
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                info!("Received from {}: {}", addr, text);
-                let _ = write.send(Message::Text(text)).await;
-            }
-            Ok(Message::Close(_)) => {
-                info!("Client disconnected: {}", addr);
-                break;
-            }
-            Err(e) => {
-                error!("Error: {}", e);
-                break;
+    'connection: loop {
+        let reply = tokio::select! {
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    info!("Received from {}: {}", addr, text);
+                    match protocol::parse(&text) {
+                        Ok((id, message)) => handle_message(id, message, &subscriber, &hub),
+                        Err(e) => {
+                            warn!("Invalid message from {}: {}", addr, e);
+                            e.into()
+                        }
+                    }
+                }
+                Some(Ok(Message::Binary(_))) => {
+                    ServerMessage::error(None, ErrorCode::UnsupportedFrame, "binary frames are not supported")
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    info!("Client disconnected: {}", addr);
+                    break;
+                }
+                Some(Err(e)) => {
+                    error!("Error: {}", e);
+                    break;
+                }
+                _ => continue,
+            },
+            update = subscriber.recv() => match update {
+                Some(update) => ServerMessage::PriceUpdate(update),
+                None => {
+                    warn!("Dropping {}: it fell too far behind", addr);
+                    let _ = write.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+        };
+
+        let mut replies = vec![reply];
+        if let Some(missed) = subscriber.take_missed() {
+            warn!("{} missed {} updates", addr, missed.count);
+            replies.push(ServerMessage::error(
+                None,
+                ErrorCode::Lagged,
+                format!(
+                    "skipped {} updates; sending the latest price of {} symbols",
+                    missed.count,
+                    missed.latest.len()
+                ),
+            ));
+            replies.extend(missed.latest.into_iter().map(ServerMessage::PriceUpdate));
+        }
+
+        for reply in &replies {
+            if let Err(e) = send(&mut write, reply).await {
+                error!("Cannot send to {}: {}", addr, e);
+                break 'connection;
             }
-            _ => {}
         }
     }
+
+    drop(subscriber);
+    info!("{} clients connected", hub.client_count());
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
     Builder::new()
         .target(Target::Stdout)
         .filter_level(LevelFilter::Info)
         .init();
 
-    let listener = TcpListener::bind("127.0.0.1:8080").await?;
-    info!("WebSocket server running on ws://127.0.0.1:8080");
+    let config = Config::from_env()?;
+    let hub = Hub::new(config.client_queue, config.on_lag);
+
+    if config.simulator.enabled {
+        tokio::spawn(Simulator::new(hub.clone(), &config.simulator).run());
+    }
+    if let Some(url) = &config.bridge.database_url {
+        tokio::spawn(bridge::run(
+            hub.clone(),
+            url.clone(),
+            config.bridge.channel.clone(),
+            config.bridge.max_age,
+        ));
+    }
+
+    let listener = TcpListener::bind(&config.addr).await?;
+    info!("WebSocket server running on ws://{}", config.addr);
 
     while let Ok((stream, _)) = listener.accept().await {
-        tokio::spawn(handle_client(stream));
+        tokio::spawn(handle_client(stream, hub.clone()));
     }
 
     Ok(())
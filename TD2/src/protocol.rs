@@ -0,0 +1,218 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Bumped on incompatible changes. Every message carries it as `"v"`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// --- Messages ---
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceUpdate {
+    pub symbol: String,
+    pub price: f64,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Messages clients send, e.g.
+/// `{"v": 1, "id": 7, "type": "subscribe", "symbols": ["AAPL"]}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Symbols may contain `*` wildcards: `*` matches every symbol,
+    /// `BTC-*` every symbol starting with `BTC-`.
+    Subscribe { symbols: Vec<String> },
+    Unsubscribe { symbols: Vec<String> },
+    /// Published to every client subscribed to the symbol.
+    PriceUpdate(PriceUpdate),
+    Ping,
+}
+
+/// Messages the server sends. `id` echoes the request being answered.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Ack {
+        id: Option<u64>,
+        /// The connection's subscriptions after the request.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        symbols: Option<Vec<String>>,
+    },
+    PriceUpdate(PriceUpdate),
+    Pong {
+        id: Option<u64>,
+    },
+    Error {
+        id: Option<u64>,
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn error(id: Option<u64>, code: ErrorCode, message: impl Into<String>) -> Self {
+        ServerMessage::Error { id, code, message: message.into() }
+    }
+
+    /// JSON text with the protocol version added.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Versioned<'a> {
+            v: u32,
+            #[serde(flatten)]
+            message: &'a ServerMessage,
+        }
+
+        serde_json::to_string(&Versioned { v: PROTOCOL_VERSION, message: self })
+            .expect("server messages serialize to JSON")
+    }
+}
+
+// --- Errors ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Not JSON at all.
+    InvalidJson,
+    /// Missing `v` or a version this server doesn't speak.
+    UnsupportedVersion,
+    /// Valid JSON that isn't a known message, or has bad fields.
+    InvalidMessage,
+    /// Only text frames are accepted.
+    UnsupportedFrame,
+    /// The client fell behind and missed messages. The latest price of each
+    /// subscribed symbol it missed follows.
+    Lagged,
+}
+
+#[derive(Debug)]
+pub struct ProtocolError {
+    /// The request's `id`, when it could be read.
+    pub id: Option<u64>,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl From<ProtocolError> for ServerMessage {
+    fn from(e: ProtocolError) -> Self {
+        ServerMessage::Error { id: e.id, code: e.code, message: e.message }
+    }
+}
+
+// --- Parsing ---
+
+/// Parses a client frame into its `id` and message.
+pub fn parse(text: &str) -> Result<(Option<u64>, ClientMessage), ProtocolError> {
+    let fail = |id, code, message: String| ProtocolError { id, code, message };
+
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| fail(None, ErrorCode::InvalidJson, e.to_string()))?;
+    let id = value.get("id").and_then(Value::as_u64);
+
+    match value.get("v").and_then(Value::as_u64) {
+        Some(v) if v == PROTOCOL_VERSION as u64 => {}
+        Some(v) => {
+            return Err(fail(
+                id,
+                ErrorCode::UnsupportedVersion,
+                format!("version {} is not supported (expected {})", v, PROTOCOL_VERSION),
+            ))
+        }
+        None => {
+            return Err(fail(
+                id,
+                ErrorCode::UnsupportedVersion,
+                format!("missing \"v\" (expected {})", PROTOCOL_VERSION),
+            ))
+        }
+    }
+
+    let mut message: ClientMessage = serde_json::from_value(value)
+        .map_err(|e| fail(id, ErrorCode::InvalidMessage, e.to_string()))?;
+
+    match &mut message {
+        ClientMessage::Subscribe { symbols } | ClientMessage::Unsubscribe { symbols } => {
+            for symbol in symbols.iter_mut() {
+                *symbol = normalize_symbol(symbol, true).map_err(|e| fail(id, ErrorCode::InvalidMessage, e))?;
+            }
+        }
+        ClientMessage::PriceUpdate(update) => {
+            update.symbol =
+                normalize_symbol(&update.symbol, false).map_err(|e| fail(id, ErrorCode::InvalidMessage, e))?;
+            if !update.price.is_finite() || update.price <= 0.0 {
+                return Err(fail(id, ErrorCode::InvalidMessage, "price must be positive".to_string()));
+            }
+        }
+        ClientMessage::Ping => {}
+    }
+
+    Ok((id, message))
+}
+
+/// Uppercases `symbol` and checks it looks like a ticker (1 to 10 letters,
+/// digits, `.` or `-`). Subscriptions may also use `*` wildcards.
+pub fn normalize_symbol(symbol: &str, wildcards: bool) -> Result<String, String> {
+    let valid = (1..=10).contains(&symbol.len())
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || (wildcards && c == '*'));
+    if valid {
+        Ok(symbol.to_ascii_uppercase())
+    } else {
+        Err(format!("invalid symbol {:?}", symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(text: &str) -> ErrorCode {
+        parse(text).unwrap_err().code
+    }
+
+    #[test]
+    fn parses_requests_and_normalizes_symbols() {
+        let (id, message) = parse(r#"{"v": 1, "id": 7, "type": "subscribe", "symbols": ["aapl", "btc-*"]}"#).unwrap();
+        assert_eq!(id, Some(7));
+        assert!(matches!(message, ClientMessage::Subscribe { symbols } if symbols == ["AAPL", "BTC-*"]));
+
+        let (id, message) = parse(r#"{"v": 1, "type": "ping"}"#).unwrap();
+        assert_eq!(id, None);
+        assert!(matches!(message, ClientMessage::Ping));
+    }
+
+    #[test]
+    fn rejects_bad_frames() {
+        assert_eq!(error_code("not json"), ErrorCode::InvalidJson);
+        assert_eq!(error_code(r#"{"type": "ping"}"#), ErrorCode::UnsupportedVersion);
+        assert_eq!(error_code(r#"{"v": 2, "type": "ping"}"#), ErrorCode::UnsupportedVersion);
+        assert_eq!(error_code(r#"{"v": 1, "type": "shout"}"#), ErrorCode::InvalidMessage);
+        assert_eq!(error_code(r#"{"v": 1, "type": "subscribe", "symbols": ["TOOLONGSYMBOL"]}"#), ErrorCode::InvalidMessage);
+    }
+
+    #[test]
+    fn price_updates_need_a_plain_symbol_and_positive_price() {
+        let update = |symbol: &str, price: f64| {
+            format!(
+                r#"{{"v": 1, "id": 3, "type": "price_update", "symbol": "{}", "price": {}, "source": "test", "timestamp": "2026-09-01T14:00:00Z"}}"#,
+                symbol, price
+            )
+        };
+        assert!(matches!(parse(&update("msft", 1.5)).unwrap().1, ClientMessage::PriceUpdate(u) if u.symbol == "MSFT"));
+
+        let err = parse(&update("*", 1.5)).unwrap_err();
+        assert_eq!((err.id, err.code), (Some(3), ErrorCode::InvalidMessage));
+        assert_eq!(error_code(&update("MSFT", 0.0)), ErrorCode::InvalidMessage);
+    }
+}
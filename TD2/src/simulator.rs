@@ -0,0 +1,138 @@
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hub::Hub;
+use crate::protocol::PriceUpdate;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    pub enabled: bool,
+    pub symbols: Vec<String>,
+    /// Time between ticks. Every symbol ticks once per interval.
+    pub interval: Duration,
+    /// Expected yearly return, e.g. 0.05 for 5%.
+    pub drift: f64,
+    /// Yearly volatility, e.g. 0.3 for 30%.
+    pub volatility: f64,
+}
+
+/// Generates prices that follow geometric Brownian motion: each tick
+/// multiplies the price by a log-normal step, so prices stay positive and
+/// moves scale with the price.
+pub struct Simulator {
+    hub: Hub,
+    interval: Duration,
+    /// Drift and standard deviation of the log return per tick.
+    mu: f64,
+    sigma: f64,
+    prices: Vec<(String, f64)>,
+    rng: StdRng,
+}
+
+impl Simulator {
+    pub fn new(hub: Hub, config: &SimulatorConfig) -> Self {
+        let mut rng = StdRng::from_entropy();
+        let dt = config.interval.as_secs_f64() / SECONDS_PER_YEAR;
+        let prices = config
+            .symbols
+            .iter()
+            .map(|s| (s.clone(), (rng.gen_range(20.0..500.0_f64) * 100.0).round() / 100.0))
+            .collect();
+
+        Self {
+            hub,
+            interval: config.interval,
+            mu: (config.drift - config.volatility.powi(2) / 2.0) * dt,
+            sigma: config.volatility * dt.sqrt(),
+            prices,
+            rng,
+        }
+    }
+
+    /// A sample from the standard normal distribution (Box-Muller).
+    fn standard_normal(&mut self) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+
+    fn tick(&mut self) {
+        let timestamp = Utc::now();
+        for i in 0..self.prices.len() {
+            let step = (self.mu + self.sigma * self.standard_normal()).exp();
+            let (symbol, price) = &mut self.prices[i];
+            *price *= step;
+
+            self.hub.publish(PriceUpdate {
+                symbol: symbol.clone(),
+                price: (*price * 10_000.0).round() / 10_000.0,
+                source: "simulator".to_string(),
+                timestamp,
+            });
+        }
+    }
+
+    /// Publishes a tick for every symbol each interval, forever.
+    pub async fn run(mut self) {
+        info!(
+            "Simulating {} symbols every {:?}",
+            self.prices.len(),
+            self.interval
+        );
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            self.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hub::LagPolicy;
+
+    fn config(volatility: f64) -> SimulatorConfig {
+        SimulatorConfig {
+            enabled: true,
+            symbols: vec!["AAPL".to_string(), "MSFT".to_string()],
+            interval: Duration::from_secs(60),
+            drift: 0.05,
+            volatility,
+        }
+    }
+
+    #[tokio::test]
+    async fn every_symbol_ticks_each_interval() {
+        let hub = Hub::new(8, LagPolicy::Drop);
+        let mut client = hub.connect();
+        client.subscribe(vec!["*".to_string()]);
+
+        let mut simulator = Simulator::new(hub, &config(0.3));
+        simulator.tick();
+
+        let mut symbols = Vec::new();
+        for _ in 0..2 {
+            let update = client.recv().await.unwrap();
+            assert_eq!(update.source, "simulator");
+            symbols.push(update.symbol);
+        }
+        assert_eq!(symbols, ["AAPL", "MSFT"]);
+    }
+
+    #[test]
+    fn prices_stay_positive() {
+        let mut simulator = Simulator::new(Hub::new(1, LagPolicy::Drop), &config(5.0));
+        for _ in 0..10_000 {
+            simulator.tick();
+        }
+        assert!(simulator.prices.iter().all(|(_, price)| *price > 0.0 && price.is_finite()));
+    }
+}